@@ -0,0 +1,173 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+// 归档格式，按输出文件的扩展名选择
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    fn from_path(path: &Path) -> Result<Self, String> {
+        let name = path.file_name()
+            .map(|n| n.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Ok(Self::TarZst)
+        } else {
+            Err(format!("Unsupported archive '{}', expected .tar, .tar.gz or .tar.zst", path.display()))
+        }
+    }
+
+    fn writer(self, file: File) -> Result<Box<dyn Write>, String> {
+        let file = BufWriter::new(file);
+        Ok(match self {
+            Self::Tar => Box::new(file),
+            Self::TarGz => Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default())),
+            Self::TarZst => Box::new(
+                zstd::Encoder::new(file, 0)
+                    .map_err(|e| format!("Failed to create zstd encoder: {}", e))?
+                    .auto_finish()
+            ),
+        })
+    }
+
+    fn reader(self, file: File) -> Result<Box<dyn Read>, String> {
+        let file = BufReader::new(file);
+        Ok(match self {
+            Self::Tar => Box::new(file),
+            Self::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+            Self::TarZst => Box::new(
+                zstd::Decoder::with_buffer(file).map_err(|e| format!("Failed to create zstd decoder: {}", e))?
+            ),
+        })
+    }
+}
+
+// 把已下载的文件打包到 output，归档内路径为 <root>/<本地相对路径>；
+// 写完后重新读取归档，确认每个文件都在且大小与元数据一致。返回归档中的文件数
+pub async fn write_archive(
+    base_path: &Path,
+    files: &[FileInfo],
+    path_map: &[PathMapping],
+    root: &str,
+    output: &Path,
+) -> Result<usize, String> {
+    let format = ArchiveFormat::from_path(output)?;
+
+    let mut entries: Vec<(PathBuf, String, u64)> = Vec::with_capacity(files.len());
+    for file in files {
+        let path = local_path(base_path, &file.rfilename, path_map);
+        let relative = path.strip_prefix(base_path)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|_| file.rfilename.clone());
+        let size = match file.size {
+            Some(size) => size,
+            None => tokio::fs::metadata(&path)
+                .await
+                .map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?
+                .len(),
+        };
+        entries.push((path, format!("{}/{}", root, relative), size));
+    }
+    entries.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let output = output.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+
+        // hub 布局下的符号链接按目标文件内容打包
+        let file = File::create(&output).map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+        let mut builder = tar::Builder::new(format.writer(file)?);
+        for (path, name, _) in &entries {
+            builder.append_path_with_name(path, name)
+                .map_err(|e| format!("Failed to add {} to {}: {}", path.display(), output.display(), e))?;
+        }
+        builder.into_inner()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+
+        verify_archive(&output, format, &entries)
+    })
+    .await
+    .map_err(|e| format!("Archive task failed: {}", e))?
+}
+
+fn verify_archive(output: &Path, format: ArchiveFormat, entries: &[(PathBuf, String, u64)]) -> Result<usize, String> {
+    let file = File::open(output).map_err(|e| format!("Failed to open {}: {}", output.display(), e))?;
+    let mut archive = tar::Archive::new(format.reader(file)?);
+    let mut sizes = HashMap::new();
+    let archived = archive.entries()
+        .map_err(|e| format!("Failed to read {}: {}", output.display(), e))?;
+    for entry in archived {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", output.display(), e))?;
+        let name = entry.path()
+            .map_err(|e| format!("Failed to read {}: {}", output.display(), e))?
+            .to_string_lossy()
+            .to_string();
+        sizes.insert(name, entry.header().size().unwrap_or(0));
+    }
+
+    for (_, name, expected) in entries {
+        match sizes.get(name) {
+            Some(size) if size == expected => {}
+            Some(size) => {
+                return Err(format!("{} in {} has size {} but expected {}", name, output.display(), size, expected));
+            }
+            None => return Err(format!("{} is missing from {}", name, output.display())),
+        }
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[tokio::test]
+    async fn archive_extracts_to_identical_files() {
+        let weights: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let repo = MockRepo::new("org/packed", &[
+            ("config.json", b"{\"layers\": 2}"),
+            ("model.bin", &weights),
+            ("tokenizer/vocab.txt", b"a\nb\nc\n"),
+        ]);
+        let server = repo.clone().serve();
+
+        for name in ["repo.tar", "repo.tar.gz", "repo.tar.zst"] {
+            let dir = tempfile::tempdir().unwrap();
+            let output = dir.path().join(name);
+            let mut config = testing::config(&server.url());
+            config.archive = Some(output.to_string_lossy().to_string());
+            testing::download(config, "org/packed", &dir.path().join("download")).await.unwrap();
+
+            let extracted = dir.path().join("extracted");
+            let format = ArchiveFormat::from_path(&output).unwrap();
+            tar::Archive::new(format.reader(File::open(&output).unwrap()).unwrap())
+                .unpack(&extracted)
+                .unwrap();
+            for (path, content) in &repo.files {
+                assert_eq!(&std::fs::read(extracted.join("packed").join(path)).unwrap(), content, "{} in {}", path, name);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_extension_is_rejected() {
+        assert!(ArchiveFormat::from_path(Path::new("repo.zip")).unwrap_err().contains("expected .tar, .tar.gz or .tar.zst"));
+        assert_eq!(ArchiveFormat::from_path(Path::new("REPO.TGZ")).unwrap(), ArchiveFormat::TarGz);
+    }
+}
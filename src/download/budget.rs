@@ -0,0 +1,116 @@
+use crate::types::FileInfo;
+use super::ignore::FilePattern;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// 设置了 max_total_size 时选择保留哪些文件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetStrategy {
+    // 按文件名顺序选取，遇到第一个放不下的文件就停止
+    #[default]
+    Order,
+    // 从小到大选取，在预算内得到尽可能多的文件
+    MostFiles,
+    // 先选匹配 budget_priority 中靠前模式的文件，放不下的跳过，继续尝试后面的文件
+    PriorityPatterns,
+}
+
+impl FromStr for BudgetStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "order" => Ok(Self::Order),
+            "most-files" => Ok(Self::MostFiles),
+            "priority-patterns" => Ok(Self::PriorityPatterns),
+            _ => Err(format!("Unknown budget strategy '{}', expected order, most-files or priority-patterns", s)),
+        }
+    }
+}
+
+// 文件所匹配的第一个优先模式的序号，都不匹配时排在最后
+fn priority(file: &FileInfo, patterns: &[FilePattern]) -> usize {
+    patterns.iter()
+        .position(|pattern| pattern.matches(&file.rfilename))
+        .unwrap_or(patterns.len())
+}
+
+// 按 strategy 选出总大小不超过 budget 的文件，结果按文件名排序；返回选中的文件和未选中的文件数。
+// 大小未知的文件按 0 计
+pub fn select_within_budget(
+    mut files: Vec<FileInfo>,
+    budget: u64,
+    strategy: BudgetStrategy,
+    patterns: &[FilePattern],
+) -> (Vec<FileInfo>, usize) {
+    files.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+    match strategy {
+        BudgetStrategy::Order => {}
+        BudgetStrategy::MostFiles => files.sort_by_key(|file| file.size.unwrap_or(0)),
+        BudgetStrategy::PriorityPatterns => files.sort_by_key(|file| priority(file, patterns)),
+    }
+
+    let total = files.len();
+    let mut used = 0u64;
+    let mut selected = Vec::with_capacity(total);
+    for file in files {
+        let size = file.size.unwrap_or(0);
+        if used + size > budget {
+            if strategy == BudgetStrategy::PriorityPatterns {
+                continue;
+            }
+            break;
+        }
+        used += size;
+        selected.push(file);
+    }
+
+    selected.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+    let skipped = total - selected.len();
+    (selected, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::ignore::{compile_patterns, FilterMode};
+
+    fn file(name: &str, size: u64) -> FileInfo {
+        FileInfo { rfilename: name.to_string(), size: Some(size), sha256: None, blob_id: None, last_modified: None }
+    }
+
+    fn names(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|f| f.rfilename.as_str()).collect()
+    }
+
+    fn repo() -> Vec<FileInfo> {
+        vec![
+            file("a-large.bin", 80),
+            file("b.json", 10),
+            file("c.txt", 10),
+            file("d.md", 10),
+        ]
+    }
+
+    #[test]
+    fn most_files_keeps_more_small_files_than_order() {
+        let (by_order, skipped) = select_within_budget(repo(), 95, BudgetStrategy::Order, &[]);
+        assert_eq!(names(&by_order), ["a-large.bin", "b.json"]);
+        assert_eq!(skipped, 2);
+
+        let (most_files, skipped) = select_within_budget(repo(), 95, BudgetStrategy::MostFiles, &[]);
+        assert_eq!(names(&most_files), ["b.json", "c.txt", "d.md"]);
+        assert_eq!(skipped, 1);
+        assert!(most_files.len() > by_order.len());
+    }
+
+    #[test]
+    fn priority_patterns_skip_what_does_not_fit() {
+        let patterns = compile_patterns(&["*.md".to_string(), "*.bin".to_string()], FilterMode::Glob).unwrap();
+        let (selected, skipped) = select_within_budget(repo(), 85, BudgetStrategy::PriorityPatterns, &patterns);
+        // d.md 先选中，a-large.bin 放不下被跳过，剩下的按顺序填满预算
+        assert_eq!(names(&selected), ["b.json", "c.txt", "d.md"]);
+        assert_eq!(skipped, 1);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use super::plan::{remaining_bytes, DownloadPlan, PlanAction};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// 下载目录的布局
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheLayout {
+    // 文件直接放在目标目录下
+    #[default]
+    Flat,
+    // 与 huggingface_hub 缓存相同：内容按摘要存放在 blobs/ 中，
+    // snapshots/<revision>/ 下是指向 blob 的符号链接，不同 revision 共用相同的 blob
+    Hub,
+}
+
+impl FromStr for CacheLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(Self::Flat),
+            "hub" => Ok(Self::Hub),
+            _ => Err(format!("Unknown cache layout '{}', expected flat or hub", s)),
+        }
+    }
+}
+
+// hub 布局下某个 revision 的快照目录：<base>/models--org--name/snapshots/<revision>
+pub fn snapshot_dir(base_path: &Path, repo_id: &str, is_dataset: bool, revision: Option<&str>) -> PathBuf {
+    let kind = if is_dataset { "datasets" } else { "models" };
+    base_path
+        .join(format!("{}--{}", kind, repo_id.replace('/', "--")))
+        .join("snapshots")
+        .join(revision.unwrap_or("main").replace('/', "-"))
+}
+
+// 快照目录所在的仓库缓存目录
+fn repo_root(snapshot: &Path) -> &Path {
+    snapshot.parent()
+        .and_then(Path::parent)
+        .unwrap_or(snapshot)
+}
+
+// blob 以 LFS sha256 命名，普通文件使用 git blob sha1，与 huggingface_hub 的 etag 一致
+fn blob_name(file: &FileInfo) -> Option<&str> {
+    file.sha256.as_deref().or(file.blob_id.as_deref())
+}
+
+// 链接内容：能算出相对路径时使用 ../../blobs/<hash> 形式，缓存目录整体移动后仍然有效
+fn link_target(snapshot: &Path, link: &Path, blob: &Path, name: &str) -> PathBuf {
+    match link.strip_prefix(snapshot) {
+        Ok(relative) => {
+            let depth = relative.components().count().saturating_sub(1) + 2;
+            let mut target = PathBuf::new();
+            for _ in 0..depth {
+                target.push("..");
+            }
+            target.join("blobs").join(name)
+        }
+        Err(_) => blob.to_path_buf(),
+    }
+}
+
+async fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        tokio::fs::symlink(target, link).await
+    }
+    #[cfg(windows)]
+    {
+        tokio::fs::symlink_file(target, link).await
+    }
+}
+
+// 在快照中创建指向 blob 的链接，替换已有的文件或链接
+async fn link_blob(snapshot: &Path, link: &Path, name: &str) -> Result<(), String> {
+    let blob = repo_root(snapshot).join("blobs").join(name);
+    let target = link_target(snapshot, link, &blob, name);
+    if tokio::fs::read_link(link).await.is_ok_and(|existing| existing == target) {
+        return Ok(());
+    }
+
+    if let Some(parent) = link.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let _ = tokio::fs::remove_file(link).await;
+    symlink(&target, link)
+        .await
+        .map_err(|e| format!("Failed to link {} to {}: {}", link.display(), blob.display(), e))
+}
+
+// 从计划中去掉已有相同 blob 的文件并为其创建链接，返回去掉的文件数。
+// 没有匹配 blob 的文件若留有旧链接则先删除，之后按普通文件下载
+pub async fn link_cached_blobs(download_plan: &mut DownloadPlan, path_map: &[PathMapping]) -> Result<usize, String> {
+    let blobs = repo_root(&download_plan.destination).join("blobs");
+    let mut files = Vec::with_capacity(download_plan.files.len());
+    let mut linked = 0;
+
+    for planned in std::mem::take(&mut download_plan.files) {
+        let link = local_path(&download_plan.destination, &planned.file.rfilename, path_map);
+        let cached = match blob_name(&planned.file) {
+            Some(name) => matches!(
+                tokio::fs::metadata(blobs.join(name)).await,
+                Ok(metadata) if metadata.is_file() && Some(metadata.len()) == planned.file.size
+            ),
+            None => false,
+        };
+        if !cached {
+            if tokio::fs::symlink_metadata(&link).await.is_ok_and(|m| m.file_type().is_symlink()) {
+                let _ = tokio::fs::remove_file(&link).await;
+            }
+            files.push(planned);
+            continue;
+        }
+
+        if let Some(name) = blob_name(&planned.file) {
+            link_blob(&download_plan.destination, &link, name).await?;
+        }
+        linked += 1;
+        if let Some(size) = planned.file.size {
+            download_plan.total_bytes -= size;
+            if planned.action == PlanAction::Download {
+                download_plan.download_bytes -= remaining_bytes(planned.local_size, size);
+            }
+        }
+    }
+
+    download_plan.files = files;
+    Ok(linked)
+}
+
+// 把下载完成的文件移入 blobs/ 并在原位置留下链接；没有摘要的文件保留为普通文件
+pub async fn store_blobs(snapshot: &Path, files: &[FileInfo], path_map: &[PathMapping]) -> Result<usize, String> {
+    let blobs = repo_root(snapshot).join("blobs");
+    tokio::fs::create_dir_all(&blobs)
+        .await
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let mut stored = 0;
+    for file in files {
+        let Some(name) = blob_name(file) else {
+            continue;
+        };
+        let path = local_path(snapshot, &file.rfilename, path_map);
+        match tokio::fs::symlink_metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => {}
+            _ => continue,
+        }
+
+        let blob = blobs.join(name);
+        if tokio::fs::metadata(&blob).await.is_ok_and(|m| m.is_file()) {
+            // 同一内容已经存在（如仓库内的重复文件），保留已有的 blob
+            tokio::fs::remove_file(&path)
+                .await
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        } else {
+            tokio::fs::rename(&path, &blob)
+                .await
+                .map_err(|e| format!("Failed to move {} to {}: {}", path.display(), blob.display(), e))?;
+        }
+        link_blob(snapshot, &path, name).await?;
+        stored += 1;
+    }
+    Ok(stored)
+}
@@ -0,0 +1,117 @@
+use super::error::DownloadError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 下载之后的处理阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    // 校验已下载文件的摘要
+    Verifying,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Verifying => "verifying",
+        }
+    }
+}
+
+// 下载进度回调，参数为 (文件名, 已下载字节数, 总字节数)；返回错误时中止整个下载
+pub trait ProgressCallback: Send + Sync {
+    fn on_progress(&self, filename: &str, downloaded: u64, total: u64) -> Result<(), String>;
+
+    // 下载之后的阶段每处理完一个文件调用一次，参数为 (阶段, 文件名, 已处理文件数, 文件总数)；默认忽略
+    fn on_phase(&self, _phase: Phase, _filename: &str, _completed: usize, _total: usize) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// 报告一个文件已处理完；回调出错时中止
+pub fn report_phase(callback: Option<&dyn ProgressCallback>, phase: Phase, filename: &str, completed: usize, total: usize) -> Result<(), String> {
+    match callback {
+        Some(callback) => callback.on_phase(phase, filename, completed, total)
+            .map_err(|e| format!("Progress callback failed: {}", e)),
+        None => Ok(()),
+    }
+}
+
+struct FileProgress {
+    downloaded: u64,
+    total: u64,
+    last_called: Option<Instant>,
+}
+
+// 按文件累计进度并节流调用回调，同一个文件两次调用之间至少间隔 interval；
+// 回调出错一次后不再调用，之后的进度更新都返回同一个错误
+pub struct ProgressReporter {
+    callback: Arc<dyn ProgressCallback>,
+    interval: Duration,
+    files: Mutex<HashMap<String, FileProgress>>,
+    error: Mutex<Option<String>>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: Arc<dyn ProgressCallback>, interval: Duration) -> Self {
+        Self {
+            callback,
+            interval,
+            files: Mutex::new(HashMap::new()),
+            error: Mutex::new(None),
+        }
+    }
+
+    // 文件开始下载，downloaded 为续传时已有的字节数
+    pub fn start(&self, filename: &str, downloaded: u64, total: u64) -> Result<(), DownloadError> {
+        self.files.lock().unwrap().insert(filename.to_string(), FileProgress {
+            downloaded,
+            total,
+            last_called: None,
+        });
+        self.report(filename, false)
+    }
+
+    pub fn advance(&self, filename: &str, bytes: u64) -> Result<(), DownloadError> {
+        if let Some(progress) = self.files.lock().unwrap().get_mut(filename) {
+            progress.downloaded += bytes;
+        }
+        self.report(filename, false)
+    }
+
+    // 完成时总是调用一次，downloaded 等于 total
+    pub fn finish(&self, filename: &str) -> Result<(), DownloadError> {
+        if let Some(progress) = self.files.lock().unwrap().get_mut(filename) {
+            progress.downloaded = progress.total;
+        }
+        let result = self.report(filename, true);
+        self.files.lock().unwrap().remove(filename);
+        result
+    }
+
+    fn report(&self, filename: &str, force: bool) -> Result<(), DownloadError> {
+        if let Some(error) = self.error.lock().unwrap().as_ref() {
+            return Err(DownloadError::Aborted(error.clone()));
+        }
+
+        let (downloaded, total) = {
+            let mut files = self.files.lock().unwrap();
+            let Some(progress) = files.get_mut(filename) else {
+                return Ok(());
+            };
+            let now = Instant::now();
+            if !force && progress.last_called.is_some_and(|last| now.duration_since(last) < self.interval) {
+                return Ok(());
+            }
+            progress.last_called = Some(now);
+            (progress.downloaded, progress.total)
+        };
+
+        // 调用回调时不持有锁，回调中可能阻塞较长时间
+        self.callback.on_progress(filename, downloaded, total).map_err(|e| {
+            let message = format!("Progress callback failed: {}", e);
+            *self.error.lock().unwrap() = Some(message.clone());
+            DownloadError::Aborted(message)
+        })
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::Config;
+use crate::types::Auth;
+use super::repo::resolve_url;
+use reqwest::Client;
+
+// 模型卡文件
+pub const README: &str = "README.md";
+
+// 模型卡中 Markdown 链接、图片以及 HTML src/href 引用的仓库内相对路径，按出现顺序去重
+pub fn asset_links(markdown: &str) -> Vec<String> {
+    let pattern = regex::Regex::new(r#"\]\(\s*<?([^)\s>]+)>?[^)]*\)|(?:src|href)\s*=\s*["']([^"']+)["']"#)
+        .expect("valid asset link pattern");
+
+    let mut links = Vec::new();
+    for captures in pattern.captures_iter(markdown) {
+        let link = captures.get(1)
+            .or_else(|| captures.get(2))
+            .map(|m| m.as_str())
+            .unwrap_or_default();
+        if let Some(path) = repo_path(link) {
+            if !links.contains(&path) {
+                links.push(path);
+            }
+        }
+    }
+    links
+}
+
+// 把相对链接转换为仓库内路径；外部链接、页内锚点和指向仓库之外的路径返回 None
+fn repo_path(link: &str) -> Option<String> {
+    if link.starts_with('#') || link.starts_with('/') || link.contains(':') {
+        return None;
+    }
+    let path = link.split(['?', '#']).next()?.replace("%20", " ");
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+// 下载模型卡内容，仓库没有 README.md 时返回 None
+pub async fn fetch_readme(
+    client: &Client,
+    config: &Config,
+    repo_id: &str,
+    auth: &Auth,
+    is_dataset: bool,
+) -> Result<Option<String>, String> {
+    let url = resolve_url(&config.endpoint, repo_id, config.revision.as_deref(), README, is_dataset);
+    let mut request = client.get(&url);
+    if let Some(token) = &auth.token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+
+    let response = request.send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", README, e))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch {}: {}", README, response.status()));
+    }
+
+    response.text()
+        .await
+        .map(Some)
+        .map_err(|e| format!("Failed to read {}: {}", README, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn asset_links_keeps_only_repo_paths() {
+        let markdown = r#"
+![architecture](./images/arch.png "Architecture")
+[paper](https://arxiv.org/abs/1234) [top](#usage) [config](<docs/my%20config.json>)
+<img src="images/arch.png" width="50%"> <a href='../outside.txt'>x</a>
+"#;
+        assert_eq!(asset_links(markdown), ["images/arch.png", "docs/my config.json"]);
+    }
+
+    #[tokio::test]
+    async fn referenced_image_is_included() {
+        let readme = b"# Model\n\n![arch](images/arch.png)\n[missing](images/missing.png)\n";
+        let repo = MockRepo::new("org/card", &[
+            ("README.md", readme),
+            ("images/arch.png", b"png"),
+            ("images/unused.png", b"png"),
+            ("model.bin", b"weights"),
+        ]);
+        let server = repo.serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.with_card_assets = true;
+
+        let (path, _) = testing::download_filtered(config, "org/card", dir.path(), &["*.md"], &[]).await.unwrap();
+        let path = std::path::Path::new(&path);
+        assert_eq!(std::fs::read(path.join("README.md")).unwrap(), readme);
+        assert_eq!(std::fs::read(path.join("images/arch.png")).unwrap(), b"png");
+        assert!(!path.join("images/unused.png").exists());
+        assert!(!path.join("model.bin").exists());
+    }
+}
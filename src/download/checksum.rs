@@ -0,0 +1,405 @@
+use crate::types::FileInfo;
+use super::callback::{report_phase, Phase, ProgressCallback};
+use super::layout::{local_path, PathMapping};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use indicatif::{ProgressBar, ProgressStyle};
+
+// 本地 blake3 快照文件，格式与 b3sum 相同：`digest  path`
+pub const BLAKE3_SNAPSHOT_FILE: &str = ".hfd-blake3";
+
+// 整个仓库的聚合摘要文件
+pub const REPO_HASH_FILE: &str = ".hfd-repo-hash";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgo {
+    // LFS 文件的 SHA256
+    Sha256,
+    // git blob SHA1（`blob <size>\0` + 内容），用于和非 LFS 文件的 blobId 比较
+    Sha1,
+    // 本地完整性快照，远端不提供
+    Blake3,
+}
+
+impl FromStr for ChecksumAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Self::Sha256),
+            "sha1" => Ok(Self::Sha1),
+            "blake3" => Ok(Self::Blake3),
+            _ => Err(format!("Unknown checksum algorithm '{}', expected sha256, sha1 or blake3", s)),
+        }
+    }
+}
+
+// 校验阶段的进度条：已校验文件数 / 总数
+pub fn verify_progress(total: usize) -> ProgressBar {
+    let pb = ProgressBar::new(total as u64);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} files {msg}")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+// 计算本地文件的摘要（十六进制小写）
+pub async fn hash_file(path: &Path, algo: ChecksumAlgo) -> Result<String, String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let read_err = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+
+        match algo {
+            ChecksumAlgo::Sha256 => {
+                let mut hasher = Sha256::new();
+                std::io::copy(&mut file, &mut hasher).map_err(read_err)?;
+                Ok(format!("{:x}", hasher.finalize()))
+            }
+            ChecksumAlgo::Sha1 => {
+                let len = file.metadata().map_err(read_err)?.len();
+                let mut hasher = Sha1::new();
+                write!(hasher, "blob {}\0", len).map_err(read_err)?;
+                std::io::copy(&mut file, &mut hasher).map_err(read_err)?;
+                Ok(format!("{:x}", hasher.finalize()))
+            }
+            ChecksumAlgo::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                std::io::copy(&mut file, &mut hasher).map_err(read_err)?;
+                Ok(hasher.finalize().to_hex().to_string())
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Hash task failed: {}", e))?
+}
+
+pub async fn sha256_file(path: &Path) -> Result<String, String> {
+    hash_file(path, ChecksumAlgo::Sha256).await
+}
+
+// 按元数据选择校验算法：优先 LFS sha256，其次 git blob sha1；可用 algo 覆盖
+pub fn expected_checksum(file: &FileInfo, algo: Option<ChecksumAlgo>) -> Option<(ChecksumAlgo, String)> {
+    match algo {
+        Some(ChecksumAlgo::Sha256) => file.sha256.clone().map(|h| (ChecksumAlgo::Sha256, h)),
+        Some(ChecksumAlgo::Sha1) => file.blob_id.clone().map(|h| (ChecksumAlgo::Sha1, h)),
+        Some(ChecksumAlgo::Blake3) => None,
+        None => file.sha256.clone()
+            .map(|h| (ChecksumAlgo::Sha256, h))
+            .or_else(|| file.blob_id.clone().map(|h| (ChecksumAlgo::Sha1, h))),
+    }
+}
+
+// verify 子命令中单个文件的检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    SizeMismatch,
+    HashMismatch,
+}
+
+impl FileStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Missing => "missing",
+            Self::SizeMismatch => "size-mismatch",
+            Self::HashMismatch => "hash-mismatch",
+        }
+    }
+}
+
+// 只读检查本地文件：先比较大小，大小一致时再与远端摘要（blake3 时与本地快照）比较，不修改快照
+pub async fn check_files(
+    base_path: &Path,
+    files: &[FileInfo],
+    path_map: &[PathMapping],
+    algo: Option<ChecksumAlgo>,
+) -> Result<Vec<FileStatus>, String> {
+    let snapshot = match algo {
+        Some(ChecksumAlgo::Blake3) => load_snapshot(&base_path.join(BLAKE3_SNAPSHOT_FILE)),
+        _ => BTreeMap::new(),
+    };
+    let pb = verify_progress(files.len());
+    let mut statuses = Vec::with_capacity(files.len());
+
+    for file in files {
+        pb.set_message(format!("Verifying {}", file.rfilename));
+        let path = local_path(base_path, &file.rfilename, path_map);
+        let status = match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => {
+                if file.size.is_some_and(|size| size != metadata.len()) {
+                    FileStatus::SizeMismatch
+                } else {
+                    let expected = match algo {
+                        Some(ChecksumAlgo::Blake3) => snapshot.get(&file.rfilename)
+                            .map(|digest| (ChecksumAlgo::Blake3, digest.clone())),
+                        _ => expected_checksum(file, algo),
+                    };
+                    match expected {
+                        Some((algo, expected)) if !hash_file(&path, algo).await?.eq_ignore_ascii_case(&expected) => {
+                            FileStatus::HashMismatch
+                        }
+                        _ => FileStatus::Ok,
+                    }
+                }
+            }
+            _ => FileStatus::Missing,
+        };
+        statuses.push(status);
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    Ok(statuses)
+}
+
+// 下载完成后的校验：有远端摘要的文件与之比较；选择 blake3 时与本地快照比较并更新快照。
+// 每校验完一个文件向进度回调报告一次 Verifying 阶段
+pub async fn verify_files(
+    base_path: &Path,
+    files: &[FileInfo],
+    path_map: &[PathMapping],
+    algo: Option<ChecksumAlgo>,
+    callback: Option<&dyn ProgressCallback>,
+) -> Result<(), String> {
+    let mut mismatches = Vec::new();
+    let pb = verify_progress(files.len());
+
+    if algo == Some(ChecksumAlgo::Blake3) {
+        let snapshot_path = base_path.join(BLAKE3_SNAPSHOT_FILE);
+        let mut snapshot = load_snapshot(&snapshot_path);
+        for (i, file) in files.iter().enumerate() {
+            pb.set_message(format!("Verifying {}", file.rfilename));
+            let digest = hash_file(&local_path(base_path, &file.rfilename, path_map), ChecksumAlgo::Blake3).await?;
+            match snapshot.get(&file.rfilename) {
+                Some(expected) if *expected != digest => {
+                    mismatches.push(format!("{}: blake3 {} != snapshot {}", file.rfilename, digest, expected));
+                }
+                _ => {
+                    snapshot.insert(file.rfilename.clone(), digest);
+                }
+            }
+            pb.inc(1);
+            report_phase(callback, Phase::Verifying, &file.rfilename, i + 1, files.len())?;
+        }
+        save_snapshot(&snapshot_path, &snapshot)?;
+    } else {
+        let mut unverified = 0;
+        for (i, file) in files.iter().enumerate() {
+            pb.set_message(format!("Verifying {}", file.rfilename));
+            if let Some((algo, expected)) = expected_checksum(file, algo) {
+                let digest = hash_file(&local_path(base_path, &file.rfilename, path_map), algo).await?;
+                if digest != expected {
+                    mismatches.push(format!("{}: {:?} {} != expected {}", file.rfilename, algo, digest, expected));
+                }
+            } else {
+                unverified += 1;
+            }
+            pb.inc(1);
+            report_phase(callback, Phase::Verifying, &file.rfilename, i + 1, files.len())?;
+        }
+        if unverified > 0 {
+            crate::warnings::warn(format!("{} file(s) have no checksum to verify against", unverified));
+        }
+    }
+    pb.finish_with_message("✓ Verification completed");
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Checksum verification failed:\n  {}", mismatches.join("\n  ")))
+    }
+}
+
+// 仓库级摘要：对按路径排序的 `path  sha256` 行整体做 sha256，内容相同的两次下载结果一致
+pub async fn repo_hash(base_path: &Path, files: &[FileInfo], path_map: &[PathMapping]) -> Result<String, String> {
+    let mut files: Vec<&FileInfo> = files.iter().collect();
+    files.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+
+    let mut hasher = Sha256::new();
+    let pb = verify_progress(files.len());
+    for file in files {
+        pb.set_message(format!("Hashing {}", file.rfilename));
+        let digest = sha256_file(&local_path(base_path, &file.rfilename, path_map)).await?;
+        hasher.update(format!("{}  {}\n", file.rfilename, digest));
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+    let hash = format!("{:x}", hasher.finalize());
+
+    let hash_path = base_path.join(REPO_HASH_FILE);
+    std::fs::write(&hash_path, format!("{}\n", hash))
+        .map_err(|e| format!("Failed to write repo hash {}: {}", hash_path.display(), e))?;
+    Ok(hash)
+}
+
+fn load_snapshot(path: &Path) -> BTreeMap<String, String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(digest, file)| (file.to_string(), digest.to_string()))
+        .collect()
+}
+
+fn save_snapshot(path: &Path, snapshot: &BTreeMap<String, String>) -> Result<(), String> {
+    let content: String = snapshot.iter()
+        .map(|(file, digest)| format!("{}  {}\n", digest, file))
+        .collect();
+    std::fs::write(path, content)
+        .map_err(|e| format!("Failed to write checksum snapshot {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    // `git hash-object` 的结果
+    const ABC_SHA1: &str = "f2ba8f84ab5c1bce84a7b441cb1959cfc7093b7f";
+    const ABC_BLAKE3: &str = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+
+    fn file_info(rfilename: &str, sha256: Option<&str>, blob_id: Option<&str>) -> FileInfo {
+        FileInfo {
+            rfilename: rfilename.to_string(),
+            size: Some(3),
+            sha256: sha256.map(String::from),
+            blob_id: blob_id.map(String::from),
+            last_modified: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn hashes_fixture_with_each_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(hash_file(&path, ChecksumAlgo::Sha256).await.unwrap(), ABC_SHA256);
+        assert_eq!(hash_file(&path, ChecksumAlgo::Sha1).await.unwrap(), ABC_SHA1);
+        assert_eq!(hash_file(&path, ChecksumAlgo::Blake3).await.unwrap(), ABC_BLAKE3);
+    }
+
+    #[test]
+    fn prefers_lfs_sha256_over_blob_sha1() {
+        let both = file_info("a", Some(ABC_SHA256), Some(ABC_SHA1));
+        assert_eq!(expected_checksum(&both, None), Some((ChecksumAlgo::Sha256, ABC_SHA256.to_string())));
+        assert_eq!(expected_checksum(&both, Some(ChecksumAlgo::Sha1)), Some((ChecksumAlgo::Sha1, ABC_SHA1.to_string())));
+        assert_eq!(expected_checksum(&both, Some(ChecksumAlgo::Blake3)), None);
+
+        let blob_only = file_info("a", None, Some(ABC_SHA1));
+        assert_eq!(expected_checksum(&blob_only, None), Some((ChecksumAlgo::Sha1, ABC_SHA1.to_string())));
+        assert_eq!(expected_checksum(&blob_only, Some(ChecksumAlgo::Sha256)), None);
+        assert_eq!("BLAKE3".parse::<ChecksumAlgo>(), Ok(ChecksumAlgo::Blake3));
+        assert!("md5".parse::<ChecksumAlgo>().is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_reports_files_that_differ() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("good.txt"), b"abc").unwrap();
+        std::fs::write(dir.path().join("bad.txt"), b"abd").unwrap();
+        let files = [file_info("good.txt", None, Some(ABC_SHA1)), file_info("bad.txt", None, Some(ABC_SHA1))];
+
+        let error = verify_files(dir.path(), &files, &[], Some(ChecksumAlgo::Sha1), None).await.unwrap_err();
+        assert!(error.contains("bad.txt: Sha1"), "{}", error);
+        assert!(!error.contains("good.txt"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn blake3_snapshot_detects_later_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("abc.txt"), b"abc").unwrap();
+        let files = [file_info("abc.txt", None, None)];
+
+        // 第一次校验只记录快照
+        verify_files(dir.path(), &files, &[], Some(ChecksumAlgo::Blake3), None).await.unwrap();
+        let snapshot = std::fs::read_to_string(dir.path().join(BLAKE3_SNAPSHOT_FILE)).unwrap();
+        assert_eq!(snapshot, format!("{}  abc.txt\n", ABC_BLAKE3));
+
+        std::fs::write(dir.path().join("abc.txt"), b"abd").unwrap();
+        assert!(verify_files(dir.path(), &files, &[], Some(ChecksumAlgo::Blake3), None).await.is_err());
+        let statuses = check_files(dir.path(), &files, &[], Some(ChecksumAlgo::Blake3)).await.unwrap();
+        assert_eq!(statuses, [FileStatus::HashMismatch]);
+    }
+
+    #[tokio::test]
+    async fn identical_downloads_have_identical_repo_hashes() {
+        use crate::testing::{self, MockRepo};
+
+        let repo = MockRepo::new("org/snapshot", &[("config.json", b"{}"), ("weights/model.bin", b"abc")]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut hashes = Vec::new();
+        for run in ["first", "second"] {
+            let server = repo.clone().serve();
+            let mut config = testing::config(&server.url());
+            config.repo_hash = true;
+            testing::download(config, "org/snapshot", &dir.path().join(run)).await.unwrap();
+            hashes.push(std::fs::read_to_string(dir.path().join(run).join("org/snapshot").join(REPO_HASH_FILE)).unwrap());
+        }
+        assert_eq!(hashes[0], hashes[1]);
+
+        // 按路径排序的 `path  sha256` 行整体的 sha256
+        let lines = format!("config.json  {}\nweights/model.bin  {}\n", testing::sha256_hex(b"{}"), ABC_SHA256);
+        assert_eq!(hashes[0].trim(), testing::sha256_hex(lines.as_bytes()));
+
+        // 期望值不一致时失败
+        let server = repo.serve();
+        let mut config = testing::config(&server.url());
+        config.verify_repo_hash = Some("0".repeat(64));
+        let error = testing::download(config, "org/snapshot", &dir.path().join("third")).await.unwrap_err();
+        assert!(testing::error_message(error).starts_with("Repo hash mismatch"));
+    }
+
+    #[tokio::test]
+    async fn verification_reports_each_file_to_the_callback() {
+        use crate::download::callback::{Phase, ProgressCallback};
+        use crate::testing::{self, MockRepo};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<(Phase, String, usize, usize)>>);
+
+        impl ProgressCallback for Recorder {
+            fn on_progress(&self, _: &str, _: u64, _: u64) -> Result<(), String> {
+                Ok(())
+            }
+
+            fn on_phase(&self, phase: Phase, filename: &str, completed: usize, total: usize) -> Result<(), String> {
+                self.0.lock().unwrap().push((phase, filename.to_string(), completed, total));
+                Ok(())
+            }
+        }
+
+        let server = MockRepo::new("org/verified", &[("a.json", b"{}"), ("b.bin", b"abc"), ("c.txt", b"text")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.verify_checksums = true;
+        let recorder = Arc::new(Recorder::default());
+        crate::cli::download_file(
+            config, "org/verified".to_string(), Some(dir.path().to_string_lossy().to_string()),
+            None, None, None, None, None, Some(recorder.clone()), crate::ShutdownHandle::new(),
+        ).await.unwrap();
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|(phase, _, _, total)| *phase == Phase::Verifying && *total == 3));
+        assert_eq!(events.iter().map(|e| e.2).collect::<Vec<_>>(), [1, 2, 3]);
+        let mut files: Vec<_> = events.iter().map(|e| e.1.as_str()).collect();
+        files.sort();
+        assert_eq!(files, ["a.json", "b.bin", "c.txt"]);
+    }
+}
+
@@ -1,15 +1,182 @@
+use rand::seq::SliceRandom;
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncWriteExt, AsyncSeekExt};
-use std::io::SeekFrom;
 use futures::StreamExt;
 use std::time::Duration;
-use crate::INTERRUPT_FLAG;
 use crate::types::FileInfo;
-use super::DownloadManager;
+use super::{refresh_interval, DownloadManager};
+use super::download_task::download_small_file;
+use super::error::DownloadError;
+use super::failures::retry_after;
+use super::sink::DownloadSink;
+use super::plan::is_complete;
+use super::progress::{commit_part, discard_partial, has_progress, part_path, ChunkProgress};
+use super::repo::resolve_url;
 
+// 自动分块时每个文件的目标块数，以及块大小的上下限
+const TARGET_CHUNKS_PER_FILE: u64 = 12;
+const MIN_AUTO_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+const MAX_AUTO_CHUNK_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+// 分块下载时请求各块的顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkOrder {
+    // 从头到尾，适合边下载边顺序读取文件的场景
+    Forward,
+    // 从尾到头，先确定文件长度，便于断点续传
+    #[default]
+    Reverse,
+    // 随机顺序，避免多个连接同时集中在 CDN 的同一段
+    Random,
+}
+
+impl FromStr for ChunkOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "forward" => Ok(Self::Forward),
+            "reverse" => Ok(Self::Reverse),
+            "random" => Ok(Self::Random),
+            _ => Err(format!("Unknown chunk order '{}', expected forward, reverse or random", s)),
+        }
+    }
+}
+
+// 按请求顺序排列块编号
+pub fn order_chunks(chunks: &mut [u64], order: ChunkOrder) {
+    match order {
+        ChunkOrder::Forward => chunks.sort_unstable(),
+        ChunkOrder::Reverse => chunks.sort_unstable_by(|a, b| b.cmp(a)),
+        ChunkOrder::Random => chunks.shuffle(&mut rand::thread_rng()),
+    }
+}
+
+// 拆分后子范围的数量和最小长度
+const SPLIT_PIECES: u64 = 4;
+const MIN_SPLIT_RANGE: u64 = 1024 * 1024;
+
+// 把 [start, end) 拆成最多 SPLIT_PIECES 个不短于 MIN_SPLIT_RANGE 的子范围，范围太小时原样返回
+fn split_range(start: u64, end: u64) -> Vec<(u64, u64)> {
+    let len = end - start;
+    let pieces = (len / MIN_SPLIT_RANGE).clamp(1, SPLIT_PIECES);
+    let piece_len = len.div_ceil(pieces);
+    (0..pieces)
+        .map(|i| (start + i * piece_len, (start + (i + 1) * piece_len).min(end)))
+        .filter(|(piece_start, piece_end)| piece_start < piece_end)
+        .collect()
+}
+
+// 请求并写入 [start, end) 一次；等待响应头受 request_timeout_secs 限制，每次读取受 stall_timeout_secs 限制，读到的字节数不足时视为网络错误
+#[allow(clippy::too_many_arguments)]
+async fn fetch_range(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+    start: u64,
+    end: u64,
+    sink: &Arc<dyn DownloadSink>,
+    download_manager: &DownloadManager,
+    filename: &str,
+    chunk_index: u64,
+    unreported_bytes: &AtomicU64,
+    last_update: &std::sync::Mutex<std::time::Instant>,
+) -> Result<(), DownloadError> {
+    let _host_permit = download_manager.host_permit(url).await;
+    // 不设置整个请求的超时：暂停期间连接保持不动，只对响应头和每次读取单独计时
+    let mut request = client.get(url)
+        .header("Range", format!("bytes={}-{}", start, end - 1));
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+
+    download_manager.stats().record_request(filename);
+    let config = download_manager.get_config();
+    let request_timeout = Duration::from_secs(config.request_timeout_secs);
+    let stall_timeout = Duration::from_secs(config.stall_timeout_secs);
+    let response = match tokio::time::timeout(request_timeout, request.send()).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(DownloadError::Network(format!("Failed to download chunk: {}", e))),
+        Err(_) => return Err(DownloadError::Network(format!("No response within {}s", config.request_timeout_secs))),
+    };
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        if let Some(wait) = retry_after(response.headers()) {
+            return Err(DownloadError::RateLimited(wait));
+        }
+    }
+    if !response.status().is_success() {
+        return Err(DownloadError::from_response(&response, &config));
+    }
+    download_manager.stats().record_source(filename, &response);
+
+    let mut stream = response.bytes_stream();
+    let mut current_pos = start;
+    let mut writer = download_manager.buffered_writer(sink.clone(), start).await;
+
+    let started = std::time::Instant::now();
+    let result = async {
+        while let Ok(Some(chunk_result)) = tokio::time::timeout(stall_timeout, stream.next()).await {
+            let chunk = chunk_result.map_err(|e| DownloadError::Network(format!("Failed to download chunk: {}", e)))?;
+            let chunk_size = chunk.len() as u64;
+
+            // 暂停时在写入前等待，连接保持不断开
+            download_manager.wait_if_paused().await;
+
+            // 写入文件
+            writer.write(&chunk).await.map_err(DownloadError::Io)?;
+
+            // 更新进度
+            current_pos += chunk_size;
+            unreported_bytes.fetch_add(chunk_size, Ordering::SeqCst);
+            download_manager.stats().record_bytes(filename, chunk_size);
+            download_manager.throttle(chunk_size).await;
+            download_manager.advance_callback(filename, chunk_size)?;
+
+            // 定期把上次更新以来各连接写入的字节数加到进度条上
+            let should_update = {
+                let mut last = last_update.lock().unwrap();
+                let now = std::time::Instant::now();
+                if now.duration_since(*last) > refresh_interval(&config) {
+                    *last = now;
+                    true
+                } else {
+                    false
+                }
+            };
+            if should_update {
+                download_manager.update_progress(filename, unreported_bytes.swap(0, Ordering::SeqCst)).await;
+            }
+        }
+        writer.flush().await.map_err(DownloadError::Io)?;
+
+        // 读取超时会提前结束循环，不完整的范围不能记为完成
+        if current_pos != end {
+            return Err(DownloadError::Network(format!(
+                "Chunk {} ended after {} of {} bytes", chunk_index, current_pos - start, end - start
+            )));
+        }
+        Ok(())
+    }.await;
+    // 记录本次连接的传输量和耗时，中途失败的连接也计入
+    download_manager.stats().record_connection(filename, chunk_index, current_pos - start, started.elapsed());
+    result
+}
+
+// 按文件大小选择块大小，使大小文件都能得到约 TARGET_CHUNKS_PER_FILE 个块，
+// 兼顾并行度与重试粒度；块大小按 1MB 对齐
+pub fn auto_chunk_size(size: u64) -> usize {
+    let chunk = size.div_ceil(TARGET_CHUNKS_PER_FILE)
+        .next_multiple_of(1024 * 1024)
+        .clamp(MIN_AUTO_CHUNK_SIZE, MAX_AUTO_CHUNK_SIZE);
+    chunk as usize
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn download_chunked_file(
     client: &Client,
     file: &FileInfo,
@@ -22,49 +189,79 @@ pub async fn download_chunked_file(
     is_dataset: bool,
     download_manager: &DownloadManager,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
-) -> Result<(), String> {
-    let size = file.size.ok_or("File size is required for chunked download")?;
+) -> Result<(), DownloadError> {
+    // 大小未知（或为 0）时无法预先分块，改为单流下载，由响应头确定大小
+    let size = match file.size {
+        Some(size) if size > 0 => size,
+        _ => {
+            return download_small_file(
+                client,
+                file,
+                path,
+                token,
+                endpoint,
+                model_id,
+                is_dataset,
+                download_manager,
+                shutdown,
+            ).await;
+        }
+    };
+    // chunk_size 为 0 时同样按文件大小自动选择
+    let chunk_size = if download_manager.get_config().auto_chunk_size || chunk_size == 0 {
+        auto_chunk_size(size)
+    } else {
+        chunk_size
+    };
 
-    // 检查文件是否已经下载
-    if let Ok(metadata) = tokio::fs::metadata(path).await {
-        if metadata.len() >= size {
-            return Ok(());
+    // 检查文件是否已经下载；有进度文件说明上次的分块下载没有完成，文件长度不可信
+    if !has_progress(path) {
+        if let Ok(metadata) = tokio::fs::metadata(path).await {
+            if is_complete(metadata.len(), Some(size)) {
+                return Ok(());
+            }
         }
     }
 
-    // 确保父目录存在
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent)
-            .await
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    let revision = download_manager.get_config().revision.clone();
+    let url = resolve_url(endpoint, model_id, revision.as_deref(), &file.rfilename, is_dataset);
+
+    // 写入本地文件时记录已完成的块，文件大小变化或临时文件不在了时从头开始；
+    // 块大小变化时把已完成的范围换算到新的块上
+    let track_progress = !download_manager.has_custom_sink();
+    let part = part_path(path);
+    let progress = ChunkProgress::load(path)
+        .filter(|p| track_progress && p.size == size && part.is_file())
+        .map(|p| if p.chunk_size == chunk_size as u64 { p } else { p.rechunk(chunk_size as u64) })
+        .unwrap_or_else(|| ChunkProgress::new(size, chunk_size as u64));
+    let resume = !progress.completed.is_empty();
+
+    // 计算需要下载的块，跳过上次已完成的块，按 chunk_order 排列
+    let mut chunks: Vec<u64> = (0..size.div_ceil(chunk_size as u64))
+        .filter(|index| !progress.completed.contains(index))
+        .collect();
+    order_chunks(&mut chunks, download_manager.get_config().chunk_order);
+    chunks.reverse(); // 下面用 pop 从末尾取块
+
+    // 在写入任何数据之前落盘进度文件，之后中途退出时文件长度不会被当作已完成
+    if track_progress {
+        progress.save(path).map_err(DownloadError::Io)?;
     }
+    download_manager.create_file_progress(file.rfilename.clone(), size).await;
+    download_manager.start_callback(&file.rfilename, progress.completed_bytes(), size)?;
+    let progress = Arc::new(std::sync::Mutex::new(progress));
 
-    let url = if is_dataset {
-        format!("{}/datasets/{}/resolve/main/{}", endpoint, model_id, file.rfilename)
-    } else {
-        format!("{}/{}/resolve/main/{}", endpoint, model_id, file.rfilename)
-    };
+    // 创建或打开写入目标，各块按偏移并发写入临时文件；没有可用的进度时清空旧的临时文件
+    let sink = download_manager.open_sink(file, &part, !resume).await.map_err(DownloadError::Io)?;
 
-    // 计算需要下载的块
-    let mut chunks: Vec<u64> = (0..((size + chunk_size as u64 - 1) / chunk_size as u64)).collect();
-    chunks.reverse(); // 从后往前下载，这样可以更好地处理断点续传
-
-    // 创建或打开文件
-    let file_handle = tokio::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(&path)
-        .await
-        .map_err(|e| format!("Failed to open file: {}", e))?;
-    let file_handle = Arc::new(tokio::sync::Mutex::new(file_handle));
-
-    // 创建共享的下载速度计数器
-    let bytes_downloaded = Arc::new(AtomicU64::new(0));
+    // 各连接已写入但还没有计入进度条的字节数
+    let unreported_bytes = Arc::new(AtomicU64::new(0));
     let last_update = Arc::new(std::sync::Mutex::new(std::time::Instant::now()));
 
     // 创建信号量来限制并发连接数
     let semaphore = Arc::new(tokio::sync::Semaphore::new(download_manager.get_config().connections_per_download));
 
+    let chunk_shutdown = shutdown.resubscribe();
     let download_task = async {
         let mut tasks = Vec::new();
 
@@ -86,100 +283,87 @@ pub async fn download_chunked_file(
             let client = client.clone();
             let url = url.clone();
             let token = token.clone();
-            let file_handle = file_handle.clone();
-            let bytes_downloaded = bytes_downloaded.clone();
+            let sink = sink.clone();
+            let progress = progress.clone();
+            let progress_file = path.clone();
+            let unreported_bytes = unreported_bytes.clone();
             let last_update = last_update.clone();
             let filename = file.rfilename.clone();
             let download_manager = download_manager.clone();
-            let mut shutdown_rx = shutdown.resubscribe();
+            let mut shutdown_rx = chunk_shutdown.resubscribe();
 
             let task = tokio::spawn(async move {
                 let _permit = permit;
-                
-                let mut retries = 0;
-                while retries < max_retries {
-                    let mut request = client.get(&url)
-                        .header("Range", format!("bytes={}-{}", start, end - 1))
-                        .timeout(std::time::Duration::from_secs(30));
-
-                    if let Some(ref token) = token {
-                        request = request.header("Authorization", format!("Bearer {}", token));
-                    }
+                let config = download_manager.get_config();
 
-                    match tokio::time::timeout(
-                        Duration::from_secs(30),
-                        request.send()
-                    ).await {
-                        Ok(Ok(response)) => {
-                            if response.status().is_success() {
-                                let mut stream = response.bytes_stream();
-                                let mut current_pos = start;
-                                
-                                let chunk_download = async {
-                                    while let Ok(Some(chunk_result)) = tokio::time::timeout(
-                                        Duration::from_secs(30),
-                                        stream.next()
-                                    ).await {
-                                        let chunk = chunk_result.map_err(|e| format!("Failed to download chunk: {}", e))?;
-                                        let chunk_size = chunk.len() as u64;
-
-                                        // 写入文件
-                                        let mut file = file_handle.lock().await;
-                                        file.seek(SeekFrom::Start(current_pos))
-                                            .await
-                                            .map_err(|e| format!("Failed to seek: {}", e))?;
-                                        file.write_all(&chunk)
-                                            .await
-                                            .map_err(|e| format!("Failed to write: {}", e))?;
-
-                                        // 更新进度
-                                        current_pos += chunk_size;
-                                        bytes_downloaded.fetch_add(chunk_size, Ordering::SeqCst);
-
-                                        // 定期更新进度条
-                                        let mut last = last_update.lock().unwrap();
-                                        let now = std::time::Instant::now();
-                                        if now.duration_since(*last).as_millis() > 100 {
-                                            download_manager.update_progress(&filename, bytes_downloaded.load(Ordering::SeqCst)).await;
-                                            *last = now;
-                                        }
-                                    }
-                                    Ok::<_, String>(())
-                                };
+                // 待下载的范围。同一范围连续失败 chunk_split_after 次后拆成更小的子范围重新请求，
+                // 子范围可能由不同的 CDN 节点提供；每个块只拆分一次
+                let mut ranges = vec![(start, end)];
+                let mut split = false;
+                while let Some((range_start, range_end)) = ranges.pop() {
+                    let mut retries = 0;
+                    loop {
+                        let result = tokio::select! {
+                            result = fetch_range(
+                                &client,
+                                &url,
+                                token.as_deref(),
+                                range_start,
+                                range_end,
+                                &sink,
+                                &download_manager,
+                                &filename,
+                                chunk_index,
+                                &unreported_bytes,
+                                &last_update,
+                            ) => result,
+                            _ = shutdown_rx.recv() => {
+                                download_manager.handle_interrupt(&filename).await;
+                                return Err(DownloadError::Interrupted);
+                            }
+                        };
 
+                        let error = match result {
+                            Ok(()) => break,
+                            // 服务器限流时按 Retry-After 等待，不计入重试次数
+                            Err(DownloadError::RateLimited(wait)) => {
+                                download_manager.stats().record_retry(&filename);
                                 tokio::select! {
-                                    result = chunk_download => {
-                                        result?;
-                                        return Ok(());
-                                    }
+                                    _ = tokio::time::sleep(wait) => continue,
                                     _ = shutdown_rx.recv() => {
                                         download_manager.handle_interrupt(&filename).await;
-                                        return Err("Download interrupted by user".to_string());
+                                        return Err(DownloadError::Interrupted);
                                     }
                                 }
                             }
-                            Err(format!("Failed to download chunk: {}", response.status()))
-                        }
-                        Ok(Err(e)) => {
-                            retries += 1;
-                            if retries >= max_retries {
-                                return Err(format!("Failed to download chunk after {} retries: {}", max_retries, e));
+                            Err(error) if !error.is_retryable(&config) => return Err(error),
+                            Err(error) => error,
+                        };
+
+                        retries += 1;
+                        if !split && config.chunk_split_after > 0 && retries >= config.chunk_split_after {
+                            let pieces = split_range(range_start, range_end);
+                            if pieces.len() > 1 {
+                                split = true;
+                                ranges.extend(pieces.into_iter().rev());
+                                download_manager.stats().record_retry(&filename);
+                                break;
                             }
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue;
                         }
-                        Err(_) => {
-                            retries += 1;
-                            if retries >= max_retries {
-                                return Err(format!("Download timed out after {} retries", max_retries));
-                            }
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue;
+                        if retries >= max_retries {
+                            return Err(error);
                         }
+                        download_manager.stats().record_retry(&filename);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
                     }
                 }
-                
-                Err("Maximum retries exceeded".to_string())
+
+                if track_progress {
+                    let mut progress = progress.lock().unwrap();
+                    progress.completed.insert(chunk_index);
+                    progress.save(&progress_file).map_err(DownloadError::Io)?;
+                }
+                Ok(())
             });
 
             tasks.push(task);
@@ -187,22 +371,255 @@ pub async fn download_chunked_file(
 
         // 等待所有任务完成
         for task in tasks {
-            task.await.map_err(|e| format!("Task failed: {}", e))??;
+            task.await.map_err(|e| DownloadError::Other(format!("Task failed: {}", e)))??;
         }
+        // 最后一次更新可能被节流跳过，补上剩余的字节
+        download_manager.update_progress(&file.rfilename, unreported_bytes.swap(0, Ordering::SeqCst)).await;
 
-        Ok::<_, String>(())
+        sink.finalize().await.map_err(DownloadError::Io)?;
+
+        // 所有块写完且大小正确后删除进度文件，再把临时文件改名为目标文件
+        if track_progress {
+            let written = tokio::fs::metadata(&part)
+                .await
+                .map(|m| m.len())
+                .map_err(|e| DownloadError::Io(format!("Failed to stat {}: {}", part.display(), e)))?;
+            if written != size {
+                return Err(DownloadError::Integrity(format!(
+                    "{} has size {} after download, expected {}", file.rfilename, written, size
+                )));
+            }
+            ChunkProgress::remove(path);
+            commit_part(path).await.map_err(DownloadError::Io)?;
+        }
+        Ok(())
     };
 
     tokio::select! {
         result = download_task => {
             result?;
             // 完成下载
+            download_manager.finish_callback(&file.rfilename)?;
             download_manager.finish_file(&file.rfilename).await;
             Ok(())
         }
         _ = shutdown.recv() => {
             download_manager.handle_interrupt(&file.rfilename).await;
-            Err("Download interrupted by user".to_string())
+            if download_manager.get_config().discard_partial_on_interrupt && !download_manager.has_custom_sink() {
+                discard_partial(path);
+            }
+            Err(DownloadError::Interrupted)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::stats::{connection_report, DownloadStats};
+
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+
+    #[test]
+    fn auto_chunk_size_targets_the_chunk_band() {
+        for size in [200 * MB, GB, 7 * GB + 123, 20 * GB] {
+            let chunk_size = auto_chunk_size(size) as u64;
+            let chunks = size.div_ceil(chunk_size);
+            assert!((8..=16).contains(&chunks), "{} bytes -> {} chunks of {}", size, chunks, chunk_size);
+            assert_eq!(chunk_size % MB, 0);
         }
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn auto_chunk_size_is_bounded() {
+        // 小文件不切成过小的块，超大文件的块不超过上限
+        assert_eq!(auto_chunk_size(10 * MB) as u64, MIN_AUTO_CHUNK_SIZE);
+        assert_eq!(auto_chunk_size(1) as u64, MIN_AUTO_CHUNK_SIZE);
+        assert_eq!(auto_chunk_size(100 * GB) as u64, MAX_AUTO_CHUNK_SIZE);
+    }
+
+    // 用 chunk_size 分块下载 content，返回本次下载的统计
+    async fn chunked_download(content: &[u8], chunk_size: usize) -> (crate::testing::MockServer, Arc<DownloadStats>) {
+        use crate::testing::{self, MockRepo};
+
+        let server = MockRepo::new("org/chunks", &[("model.bin", content)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.bin");
+        let config = testing::config(&server.url());
+        let stats = Arc::new(DownloadStats::new());
+        let manager = DownloadManager::new(content.len() as u64, config.clone()).with_stats(stats.clone());
+        let file = FileInfo {
+            rfilename: "model.bin".to_string(),
+            size: Some(content.len() as u64),
+            sha256: None,
+            blob_id: None,
+            last_modified: None,
+        };
+        let (_shutdown, receiver) = tokio::sync::broadcast::channel(1);
+
+        download_chunked_file(
+            &Client::new(), &file, &path, chunk_size, config.max_retries, None,
+            &config.endpoint, "org/chunks", false, &manager, receiver,
+        ).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        (server, stats)
+    }
+
+    #[tokio::test]
+    async fn connection_bytes_sum_to_the_file_size() {
+        let content: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let (_server, stats) = chunked_download(&content, 300).await;
+
+        let connections = stats.connections();
+        let mut chunks: Vec<u64> = connections.iter().map(|c| c.chunk).collect();
+        chunks.sort_unstable();
+        assert_eq!(chunks, [0, 1, 2, 3]);
+        assert_eq!(connections.iter().map(|c| c.bytes).sum::<u64>(), content.len() as u64);
+        let report = connection_report(&connections).unwrap();
+        assert!(report.starts_with("4 range requests: median "), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn configured_chunk_size_sets_the_range_count() {
+        use crate::testing::{self, MockRepo};
+
+        let content: Vec<u8> = (0..100u8).collect();
+        let server = MockRepo::new("org/tiny", &[("model.bin", &content)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 16;
+        config.chunk_size = 16;
+        testing::download(config, "org/tiny", dir.path()).await.unwrap();
+
+        let mut ranges: Vec<_> = server.requests().iter().filter(|r| r.method == "GET").filter_map(|r| r.range()).collect();
+        ranges.sort();
+        assert_eq!(ranges.len(), 7);
+        assert_eq!(ranges[0], (0, Some(15)));
+        assert_eq!(ranges[6], (96, Some(99)));
+        assert_eq!(std::fs::read(dir.path().join("org/tiny/model.bin")).unwrap(), content);
+    }
+
+    #[test]
+    fn order_chunks_follows_the_order() {
+        let mut chunks = vec![2, 0, 3, 1];
+        order_chunks(&mut chunks, ChunkOrder::Forward);
+        assert_eq!(chunks, [0, 1, 2, 3]);
+        order_chunks(&mut chunks, ChunkOrder::Reverse);
+        assert_eq!(chunks, [3, 2, 1, 0]);
+        order_chunks(&mut chunks, ChunkOrder::Random);
+        chunks.sort_unstable();
+        assert_eq!(chunks, [0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn chunks_are_requested_in_the_configured_order() {
+        use crate::testing::{self, MockRepo};
+
+        let content: Vec<u8> = (0..64u8).collect();
+        for (order, expected) in [(ChunkOrder::Forward, [0, 16, 32, 48]), (ChunkOrder::Reverse, [48, 32, 16, 0])] {
+            let server = MockRepo::new("org/order", &[("model.bin", &content)]).serve();
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = testing::config(&server.url());
+            config.parallel_download_threshold = 16;
+            config.chunk_size = 16;
+            // 单连接时请求顺序就是块的顺序
+            config.connections_per_download = 1;
+            config.chunk_order = order;
+            testing::download(config, "org/order", dir.path()).await.unwrap();
+
+            let starts: Vec<u64> = server.requests().iter().filter_map(|r| r.range()).map(|(start, _)| start).collect();
+            assert_eq!(starts, expected, "{:?}", order);
+            assert_eq!(std::fs::read(dir.path().join("org/order/model.bin")).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn split_range_makes_at_most_four_pieces() {
+        assert_eq!(split_range(0, 8 * MB), [(0, 2 * MB), (2 * MB, 4 * MB), (4 * MB, 6 * MB), (6 * MB, 8 * MB)]);
+        assert_eq!(split_range(MB, 3 * MB + 1), [(MB, 2 * MB + 1), (2 * MB + 1, 3 * MB + 1)]);
+        // 不足两个最小子范围时不拆分
+        assert_eq!(split_range(10, 10 + MB), [(10, 10 + MB)]);
+        assert_eq!(split_range(0, 100), [(0, 100)]);
+    }
+
+    #[tokio::test]
+    async fn failing_range_succeeds_once_split() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+
+        let content: Vec<u8> = (0..4 * MB).map(|i| (i % 251) as u8).collect();
+        let len = content.len() as u64;
+        let repo = MockRepo::new("org/split", &[("model.bin", &content)]);
+        // 整块的范围总是失败，拆分后的子范围正常返回
+        let server = MockServer::start(move |request| match request.range() {
+            Some((0, Some(end))) if end + 1 == len => Response::new(503),
+            _ => repo.respond(request).unwrap_or_else(Response::not_found),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = MB;
+        config.chunk_size = 4 * MB as usize;
+        config.chunk_split_after = 2;
+        testing::download(config, "org/split", dir.path()).await.unwrap();
+
+        let ranges: Vec<_> = server.requests().iter().filter_map(|r| r.range()).collect();
+        assert_eq!(ranges[..2], [(0, Some(len - 1)), (0, Some(len - 1))]);
+        let mut pieces = ranges[2..].to_vec();
+        pieces.sort();
+        assert_eq!(pieces, [(0, Some(MB - 1)), (MB, Some(2 * MB - 1)), (2 * MB, Some(3 * MB - 1)), (3 * MB, Some(len - 1))]);
+        assert_eq!(std::fs::read(dir.path().join("org/split/model.bin")).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn total_progress_matches_the_summed_sizes() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+
+        let first: Vec<u8> = (0..40_000u32).map(|i| (i % 251) as u8).collect();
+        let second: Vec<u8> = (0..25_000u32).map(|i| (i % 241) as u8).collect();
+        let repo = MockRepo::new("org/total", &[("a.bin", &first), ("b.bin", &second)]);
+        // 慢速返回，让节流后的进度更新在下载过程中多次发生
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).paced(512, Duration::from_millis(25))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let config = testing::config(&server.url());
+        let manager = DownloadManager::new(0, config.clone());
+        let download = |name: &'static str, size: usize| {
+            let file = FileInfo { rfilename: name.to_string(), size: Some(size as u64), sha256: None, blob_id: None, last_modified: None };
+            let path = dir.path().join(name);
+            let manager = &manager;
+            let config = &config;
+            async move {
+                let (_shutdown, receiver) = tokio::sync::broadcast::channel(1);
+                download_chunked_file(
+                    &Client::new(), &file, &path, 4096, config.max_retries, None,
+                    &config.endpoint, "org/total", false, manager, receiver,
+                ).await
+            }
+        };
+
+        // 下载过程中汇总进度不能超过两个文件的大小之和；完成时进度条会被设为满格，只能在过程中检查
+        let total = (first.len() + second.len()) as u64;
+        let done = std::sync::atomic::AtomicBool::new(false);
+        let watch = async {
+            let mut max_seen = 0;
+            while !done.load(Ordering::SeqCst) {
+                max_seen = max_seen.max(manager.total_progress.position());
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            max_seen
+        };
+        let downloads = async {
+            let result = tokio::join!(download("a.bin", first.len()), download("b.bin", second.len()));
+            done.store(true, Ordering::SeqCst);
+            result
+        };
+        let ((a, b), max_seen) = tokio::join!(downloads, watch);
+        a.unwrap();
+        b.unwrap();
+        assert!(max_seen > 0);
+        assert!(max_seen <= total, "progress reached {} of {}", max_seen, total);
+        assert_eq!(manager.total_progress.length(), Some(total));
+        assert_eq!(manager.total_progress.position(), total);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+// 把匹配 pattern 的分片（如 model.tar.gz.part-aa、part-ab）按文件名顺序拼接到 output，
+// 返回拼接后的大小
+pub async fn concat_files(
+    base_path: &Path,
+    files: &[FileInfo],
+    path_map: &[PathMapping],
+    pattern: &str,
+    output: &Path,
+) -> Result<u64, String> {
+    let pattern = glob::Pattern::new(pattern)
+        .map_err(|e| format!("Invalid concat pattern '{}': {}", pattern, e))?;
+
+    let mut parts: Vec<&FileInfo> = files.iter()
+        .filter(|f| pattern.matches(&f.rfilename))
+        .collect();
+    if parts.is_empty() {
+        return Err(format!("No downloaded files match concat pattern '{}'", pattern));
+    }
+    parts.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+
+    if let Some(parent) = output.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let mut out = tokio::fs::File::create(output)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+
+    let mut expected = 0;
+    for part in &parts {
+        let path = local_path(base_path, &part.rfilename, path_map);
+        let mut input = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let copied = tokio::io::copy(&mut input, &mut out)
+            .await
+            .map_err(|e| format!("Failed to append {} to {}: {}", path.display(), output.display(), e))?;
+        expected += part.size.unwrap_or(copied);
+    }
+    out.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+
+    let size = tokio::fs::metadata(output)
+        .await
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to stat {}: {}", output.display(), e))?;
+    if size != expected {
+        return Err(format!(
+            "Concatenated file {} has size {} but its {} parts add up to {}",
+            output.display(), size, parts.len(), expected
+        ));
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{self, MockRepo};
+
+    #[tokio::test]
+    async fn concatenates_parts_in_name_order() {
+        let server = MockRepo::new("org/split", &[
+            ("README.md", b"readme"),
+            ("model.tar.gz.part-ab", b"second half"),
+            ("model.tar.gz.part-aa", b"first half, "),
+        ]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("joined/model.tar.gz");
+
+        // 分片不在 include 中也会被下载
+        let mut config = testing::config(&server.url());
+        config.concat = Some(("model.tar.gz.part-*".to_string(), output.to_string_lossy().to_string()));
+        testing::download_filtered(config, "org/split", &dir.path().join("out"), &["README.md"], &[]).await.unwrap();
+
+        assert_eq!(std::fs::read(&output).unwrap(), b"first half, second half");
+        assert!(dir.path().join("out/org/split/model.tar.gz.part-aa").is_file());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_pattern_without_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = super::concat_files(dir.path(), &[], &[], "*.part-*", &dir.path().join("out")).await.unwrap_err();
+        assert!(error.starts_with("No downloaded files match"), "{}", error);
+    }
+}
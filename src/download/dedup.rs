@@ -0,0 +1,97 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use super::plan::{DownloadPlan, PlanAction};
+use std::collections::HashMap;
+use std::path::Path;
+
+// 内容与另一个文件相同的待下载文件，下载完成后从 source 链接或复制
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    pub file: FileInfo,
+    pub source: String,
+}
+
+// 按元数据中的摘要找出内容相同的文件：每组只下载一份，其余记为 Duplicate
+pub fn split_duplicates(download_plan: &DownloadPlan) -> (Vec<FileInfo>, Vec<Duplicate>) {
+    let mut sources: HashMap<&str, &str> = HashMap::new();
+    let mut files = Vec::with_capacity(download_plan.files.len());
+    let mut duplicates = Vec::new();
+
+    for planned in &download_plan.files {
+        let file = &planned.file;
+        let hash = file.sha256.as_deref().or(file.blob_id.as_deref());
+        let source = hash.and_then(|hash| sources.get(hash).copied());
+
+        match source {
+            // 只有尚未下载的文件才用副本代替，已存在的文件保持不动
+            Some(source) if planned.action == PlanAction::Download => {
+                duplicates.push(Duplicate {
+                    file: file.clone(),
+                    source: source.to_string(),
+                });
+            }
+            _ => {
+                if let Some(hash) = hash {
+                    sources.entry(hash).or_insert(&file.rfilename);
+                }
+                files.push(file.clone());
+            }
+        }
+    }
+
+    (files, duplicates)
+}
+
+// 为重复文件创建硬链接，跨文件系统等无法链接时退回复制
+pub async fn link_duplicates(base_path: &Path, duplicates: &[Duplicate], path_map: &[PathMapping]) -> Result<(), String> {
+    for duplicate in duplicates {
+        let source = local_path(base_path, &duplicate.source, path_map);
+        let target = local_path(base_path, &duplicate.file.rfilename, path_map);
+
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+        // 先删除可能残留的部分下载
+        let _ = tokio::fs::remove_file(&target).await;
+
+        if tokio::fs::hard_link(&source, &target).await.is_err() {
+            tokio::fs::copy(&source, &target)
+                .await
+                .map_err(|e| format!("Failed to copy {} to {}: {}", source.display(), target.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{self, MockRepo};
+
+    #[tokio::test]
+    async fn identical_files_are_downloaded_once() {
+        let server = MockRepo::new("org/dupes", &[
+            ("a/config.json", b"{\"same\": true}"),
+            ("b/config.json", b"{\"same\": true}"),
+            ("other.json", b"{}"),
+        ]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.dedup_identical = true;
+        testing::download(config, "org/dupes", dir.path()).await.unwrap();
+
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["a/config.json", "other.json"]);
+
+        let base = dir.path().join("org/dupes");
+        assert_eq!(std::fs::read(base.join("b/config.json")).unwrap(), b"{\"same\": true}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |name: &str| std::fs::metadata(base.join(name)).unwrap().ino();
+            assert_eq!(inode("a/config.json"), inode("b/config.json"));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use indicatif::HumanBytes;
+use std::path::Path;
+
+/// 下载前检查磁盘空间，确保下载完成后仍保留 `min_free_after` 字节的空闲空间
+pub fn check_disk_space(path: &Path, required: u64, min_free_after: u64) -> Result<(), String> {
+    let available = fs2::available_space(path)
+        .map_err(|e| format!("Failed to query free disk space for {}: {}", path.display(), e))?;
+
+    if available < required.saturating_add(min_free_after) {
+        if min_free_after > 0 && available >= required {
+            return Err(format!(
+                "Download of {} would leave only {} free on {}, below the required headroom of {} (min_free_after)",
+                HumanBytes(required),
+                HumanBytes(available - required),
+                path.display(),
+                HumanBytes(min_free_after),
+            ));
+        }
+        return Err(format!(
+            "Not enough disk space on {}: need {} (plus {} headroom), but only {} available",
+            path.display(),
+            HumanBytes(required),
+            HumanBytes(min_free_after),
+            HumanBytes(available),
+        ));
+    }
+
+    Ok(())
+}
+
+/// 解析形如 `10G`、`10GB`、`512M`、`1024` 的大小字符串（单位为 1024 进制）
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let number = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1u64 << 10),
+        Some('M') => (&number[..number.len() - 1], 1u64 << 20),
+        Some('G') => (&number[..number.len() - 1], 1u64 << 30),
+        Some('T') => (&number[..number.len() - 1], 1u64 << 40),
+        _ => (number, 1),
+    };
+
+    number.trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("Invalid size '{}'", value))
+}
+
+/// 下载前检查目标路径：已存在但不是普通文件（目录、符号链接等）时报错，
+/// `replace` 为 true 时删除后重新下载
+pub fn check_conflicting_paths(base_path: &Path, files: &[FileInfo], path_map: &[PathMapping], replace: bool) -> Result<(), String> {
+    let mut conflicts = Vec::new();
+    for file in files {
+        let path = local_path(base_path, &file.rfilename, path_map);
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.is_file() {
+            continue;
+        }
+
+        let kind = if metadata.file_type().is_symlink() {
+            "a symlink"
+        } else if metadata.is_dir() {
+            "a directory"
+        } else {
+            "not a regular file"
+        };
+
+        if replace {
+            let removed = if metadata.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            removed.map_err(|e| format!("Failed to remove {} ({}): {}", path.display(), kind, e))?;
+        } else {
+            conflicts.push(format!("{} is {}", path.display(), kind));
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Destination paths exist but are not regular files (use --replace-conflicting to remove them):\n  {}",
+            conflicts.join("\n  ")
+        ))
+    }
+}
+
+/// 下载前检查目标目录：非空且既没有 hfd 留下的文件（`.hfd*`、`.downloading`）也没有本仓库的文件时，
+/// 很可能指错了目录，除非 `merge` 为 true 否则报错
+pub fn check_destination(base_path: &Path, files: &[FileInfo], path_map: &[PathMapping], merge: bool) -> Result<(), String> {
+    let entries: Vec<_> = match std::fs::read_dir(base_path) {
+        Ok(entries) => entries.filter_map(Result::ok).collect(),
+        Err(_) => return Ok(()),
+    };
+    if merge || entries.is_empty() {
+        return Ok(());
+    }
+
+    let has_marker = entries.iter().any(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        name.starts_with(".hfd") || name == super::progress::DOWNLOADING_MARKER
+    });
+    // 中断的下载只留下 .hfd-part，同样算作此前的下载
+    let has_repo_file = files.iter()
+        .map(|file| local_path(base_path, &file.rfilename, path_map))
+        .any(|path| path.exists() || super::progress::part_path(&path).exists());
+    if has_marker || has_repo_file {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{} is not empty and does not look like a previous download of this repository ({} unrelated entries); \
+         pass --merge to download into it anyway",
+        base_path.display(),
+        entries.len()
+    ))
+}
+
+/// 下载结束后按解析出的文件列表检查本地文件，返回不在磁盘上的文件，用来发现被吞掉的失败
+pub fn missing_files(base_path: &Path, files: &[FileInfo], path_map: &[PathMapping]) -> Vec<String> {
+    files.iter()
+        .filter(|file| !local_path(base_path, &file.rfilename, path_map).is_file())
+        .map(|file| file.rfilename.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_download_that_fits_but_violates_headroom() {
+        let dir = tempfile::tempdir().unwrap();
+        let available = fs2::available_space(dir.path()).unwrap();
+
+        // 下载本身放得下，但剩余空间会低于 min_free_after
+        let error = check_disk_space(dir.path(), available / 2, available).unwrap_err();
+        assert!(error.contains("below the required headroom"), "{}", error);
+        assert!(error.contains("min_free_after"), "{}", error);
+
+        assert!(check_disk_space(dir.path(), 0, 0).is_ok());
+    }
+
+    #[test]
+    fn rejects_download_larger_than_free_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let available = fs2::available_space(dir.path()).unwrap();
+
+        let error = check_disk_space(dir.path(), available + 1, 0).unwrap_err();
+        assert!(error.starts_with("Not enough disk space"), "{}", error);
+    }
+
+    #[test]
+    fn parses_sizes_with_binary_units() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("20G"), Ok(20 << 30));
+        assert_eq!(parse_size("512mb"), Ok(512 << 20));
+        assert_eq!(parse_size(" 4K "), Ok(4096));
+        assert!(parse_size("ten").is_err());
+    }
+
+    fn file(name: &str) -> FileInfo {
+        FileInfo { rfilename: name.to_string(), size: Some(4), sha256: None, blob_id: None, last_modified: None }
+    }
+
+    #[test]
+    fn non_empty_unrelated_directory_needs_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [file("config.json"), file("model.bin")];
+        assert!(check_destination(dir.path(), &files, &[], false).is_ok());
+
+        std::fs::write(dir.path().join("notes.txt"), "mine").unwrap();
+        let error = check_destination(dir.path(), &files, &[], false).unwrap_err();
+        assert!(error.contains("--merge"), "{}", error);
+        assert!(error.contains("1 unrelated entries"), "{}", error);
+        assert!(check_destination(dir.path(), &files, &[], true).is_ok());
+    }
+
+    #[test]
+    fn previous_download_is_recognized() {
+        let files = [file("config.json"), file("model.bin")];
+
+        let complete = tempfile::tempdir().unwrap();
+        std::fs::write(complete.path().join("notes.txt"), "mine").unwrap();
+        std::fs::write(complete.path().join("config.json"), "{}").unwrap();
+        assert!(check_destination(complete.path(), &files, &[], false).is_ok());
+
+        // 中断的下载只留下 .hfd-part
+        let interrupted = tempfile::tempdir().unwrap();
+        std::fs::write(interrupted.path().join("model.bin.hfd-part"), "mo").unwrap();
+        assert!(check_destination(interrupted.path(), &files, &[], false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn download_into_unrelated_directory_is_refused() {
+        use crate::testing::{self, MockRepo};
+
+        let server = MockRepo::new("org/repo", &[("config.json", b"{}")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("org/repo")).unwrap();
+        std::fs::write(dir.path().join("org/repo/notes.txt"), "mine").unwrap();
+
+        let error = testing::download(testing::config(&server.url()), "org/repo", dir.path()).await.unwrap_err();
+        assert!(testing::error_message(error).contains("--merge"));
+        assert!(server.downloads().is_empty());
+
+        let mut config = testing::config(&server.url());
+        config.merge = true;
+        testing::download(config, "org/repo", dir.path()).await.unwrap();
+        assert_eq!(std::fs::read(dir.path().join("org/repo/config.json")).unwrap(), b"{}");
+        assert_eq!(std::fs::read(dir.path().join("org/repo/notes.txt")).unwrap(), b"mine");
+    }
+
+    #[test]
+    fn directory_at_a_file_path_is_a_clear_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [file("config.json"), file("model.safetensors")];
+        std::fs::create_dir(dir.path().join("model.safetensors")).unwrap();
+        std::fs::write(dir.path().join("config.json"), "{}").unwrap();
+
+        let error = check_conflicting_paths(dir.path(), &files, &[], false).unwrap_err();
+        assert!(error.contains("model.safetensors is a directory"), "{}", error);
+        assert!(error.contains("--replace-conflicting"), "{}", error);
+        assert!(!error.contains("config.json"), "{}", error);
+
+        check_conflicting_paths(dir.path(), &files, &[], true).unwrap();
+        assert!(!dir.path().join("model.safetensors").exists());
+        assert!(dir.path().join("config.json").is_file());
+    }
+
+    #[tokio::test]
+    async fn download_fails_early_on_a_conflicting_directory() {
+        use crate::testing::{self, MockRepo};
+
+        let server = MockRepo::new("org/repo", &[("config.json", b"{}"), ("model.safetensors", b"weights")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("org/repo/model.safetensors")).unwrap();
+
+        let error = testing::download(testing::config(&server.url()), "org/repo", dir.path()).await.unwrap_err();
+        assert!(testing::error_message(error).contains("model.safetensors is a directory"));
+        assert!(server.downloads().is_empty());
+
+        let mut config = testing::config(&server.url());
+        config.replace_conflicting = true;
+        testing::download(config, "org/repo", dir.path()).await.unwrap();
+        assert_eq!(std::fs::read(dir.path().join("org/repo/model.safetensors")).unwrap(), b"weights");
+    }
+
+    #[tokio::test]
+    async fn silently_missing_file_fails_the_count_check() {
+        use crate::download::transform::StreamTransform;
+        use crate::testing::{self, MockRepo};
+        use std::sync::Arc;
+
+        let server = MockRepo::new("org/dropped", &[("a.txt", b"first"), ("b.txt", b"second")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("org/dropped");
+        let download = |verify_file_count: bool| {
+            let mut config = testing::config(&server.url());
+            config.concurrent_downloads = 1;
+            config.verify_file_count = verify_file_count;
+            // 下载第二个文件时删掉已完成的第一个，模拟一个报告成功却没有落盘的文件
+            let base = base.clone();
+            let drop_other: Arc<dyn StreamTransform> = Arc::new(move |chunk: &[u8]| {
+                let other = if chunk == b"first" { "b.txt" } else { "a.txt" };
+                let _ = std::fs::remove_file(base.join(other));
+                chunk.to_vec()
+            });
+            crate::cli::download_file(
+                config, "org/dropped".to_string(), Some(dir.path().to_string_lossy().to_string()),
+                None, None, None, Some(drop_other), None, None, crate::ShutdownHandle::new(),
+            )
+        };
+
+        // 不核对时这种丢失不会被发现
+        download(false).await.unwrap();
+        let present = ["a.txt", "b.txt"].iter().filter(|name| base.join(name).is_file()).count();
+        assert_eq!(present, 1);
+
+        std::fs::remove_dir_all(&base).unwrap();
+        let error = testing::error_message(download(true).await.unwrap_err());
+        assert!(error.contains("1 of 2 expected files are missing after download"), "{}", error);
+    }
+}
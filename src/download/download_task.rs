@@ -1,15 +1,26 @@
 use crate::types::FileInfo;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use reqwest::Client;
-use pyo3::prelude::*;
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
-use std::io::SeekFrom;
-use std::time::Duration;
+use futures::StreamExt;
 use tokio::fs;
+use crate::download::callback::ProgressCallback;
 use crate::download::chunk::download_chunked_file;
 use crate::download::DownloadManager;
-use crate::INTERRUPT_FLAG;
+use crate::download::layout::local_path;
+use crate::download::lfs;
+use crate::download::plan::is_complete;
+use crate::download::checksum::{expected_checksum, hash_file};
+use crate::download::progress::{commit_part, discard_partial, has_progress, local_progress, part_path, part_size, ChunkProgress};
+use crate::download::repo::resolve_url;
+use crate::download::error::DownloadError;
+use crate::download::failures::{is_retryable_status, retry_after, FailedFile};
+use crate::download::sink::SinkProvider;
+use crate::download::stats::DownloadStats;
+use crate::download::transform::StreamTransform;
+use crate::config::Config;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn download_small_file(
     client: &Client,
     file: &FileInfo,
@@ -20,127 +31,238 @@ pub async fn download_small_file(
     is_dataset: bool,
     download_manager: &DownloadManager,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
-) -> Result<(), String> {
-    // 检查文件是否已经下载
-    if let Some(size) = file.size {
+) -> Result<(), DownloadError> {
+    // 检查文件是否已经下载；留有分块进度文件时文件中可能有空洞，需要整体重新下载。
+    // 变换后的文件已在下载计划中按记录排除，到这里的总是需要重新下载
+    let stale_progress = has_progress(path);
+    if !stale_progress && download_manager.transform().is_none() {
         if let Ok(metadata) = tokio::fs::metadata(path).await {
-            if metadata.len() >= size {
+            if is_complete(metadata.len(), file.size) {
                 return Ok(());
             }
         }
     }
 
-    // 确保父目录存在
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent)
-            .await
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
-    }
-
-    let url = if is_dataset {
-        format!("{}/datasets/{}/resolve/main/{}", endpoint, model_id, file.rfilename)
-    } else {
-        format!("{}/{}/resolve/main/{}", endpoint, model_id, file.rfilename)
-    };
+    let revision = download_manager.get_config().revision.clone();
+    let url = resolve_url(endpoint, model_id, revision.as_deref(), &file.rfilename, is_dataset);
 
-    let mut request = client.get(&url);
-    if let Some(ref token) = token {
-        request = request.header("Authorization", format!("Bearer {}", token));
+    // 获取临时文件中已下载的大小；设置了字节流变换或自定义写入目标时本地文件与远端偏移不对应，总是从头下载。
+    // 临时文件已达到远端大小说明上次在校验前中断，同样从头下载
+    let mut downloaded_size = 0;
+    if let (None, false, false) = (download_manager.transform(), download_manager.has_custom_sink(), stale_progress) {
+        downloaded_size = part_size(path);
+        if file.size.is_some_and(|size| size > 0 && downloaded_size >= size) {
+            downloaded_size = 0;
+        }
     }
 
-    // 获取已下载的大小
-    let mut downloaded_size = 0;
-    if let Ok(metadata) = tokio::fs::metadata(path).await {
-        if metadata.len() > 0 {
-            downloaded_size = metadata.len();
+    // 按主机限制并发时，许可保持到响应体读完
+    let _host_permit = download_manager.host_permit(&url).await;
+
+    // 连接失败和 retry_statuses 中的状态码最多重试 max_retries 次
+    let config = download_manager.get_config();
+    let request_timeout = std::time::Duration::from_secs(config.request_timeout_secs);
+    let stall_timeout = std::time::Duration::from_secs(config.stall_timeout_secs);
+    let mut retries = 0;
+    let response = loop {
+        let mut request = client.get(&url);
+        if let Some(ref token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        if downloaded_size > 0 {
             request = request.header("Range", format!("bytes={}-", downloaded_size));
         }
-    }
 
-    let response = request.send()
-        .await
-        .map_err(|e| format!("Failed to download file: {}", e))?;
+        download_manager.stats().record_request(&file.rfilename);
+        let error = match tokio::time::timeout(request_timeout, request.send()).await {
+            Err(_) => DownloadError::Network(format!("No response within {}s", config.request_timeout_secs)),
+            Ok(Ok(response)) => {
+                let status = response.status();
+                if status.is_success() {
+                    download_manager.stats().record_source(&file.rfilename, &response);
+                    break response;
+                }
+                // 服务器限流时按 Retry-After 等待，不计入重试次数
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    if let Some(wait) = retry_after(response.headers()) {
+                        download_manager.stats().record_retry(&file.rfilename);
+                        tokio::time::sleep(wait).await;
+                        continue;
+                    }
+                }
+                if !is_retryable_status(status, &config) {
+                    return Err(DownloadError::from_response(&response, &config));
+                }
+                DownloadError::HttpStatus(status.as_u16())
+            }
+            Ok(Err(e)) => DownloadError::Network(format!("Failed to download file after {} retries: {}", config.max_retries, e)),
+        };
+
+        retries += 1;
+        if retries >= config.max_retries {
+            return Err(error);
+        }
+        download_manager.stats().record_retry(&file.rfilename);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    };
+
+    // 服务器忽略 Range 返回 200 时响应体是完整文件，清空临时文件从头写入；206 时接在已有内容之后
+    if downloaded_size > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        downloaded_size = 0;
+    }
 
     // 获取文件总大小
-    let total_size = if let Some(size) = file.size {
-        size
-    } else if let Some(content_length) = response.content_length() {
-        content_length + downloaded_size
-    } else {
-        return Err("Could not determine file size".to_string());
+    // 元数据中的 0 可能表示大小未知，有响应头时以响应头为准
+    let total_size = match (file.size, response.content_length()) {
+        (Some(size), _) if size > 0 => size,
+        (_, Some(content_length)) => content_length + downloaded_size,
+        (Some(size), None) => size,
+        (None, None) => return Err(DownloadError::Other("Could not determine file size".to_string())),
     };
 
     // 创建进度条
     let _pb = download_manager.create_file_progress(file.rfilename.clone(), total_size).await;
+    download_manager.start_callback(&file.rfilename, downloaded_size, total_size)?;
 
-    let mut output_file = if downloaded_size > 0 {
-        let mut file = tokio::fs::OpenOptions::new()
-            .write(true)
-            .open(path)
-            .await
-            .map_err(|e| format!("Failed to open file: {}", e))?;
-        
-        file.seek(SeekFrom::Start(downloaded_size))
-            .await
-            .map_err(|e| format!("Failed to seek: {}", e))?;
-        
-        file
-    } else {
-        tokio::fs::File::create(path)
-            .await
-            .map_err(|e| format!("Failed to create file: {}", e))?
-    };
+    // 续传时保留已有内容，从 downloaded_size 处继续写入
+    let part = part_path(path);
+    let sink = download_manager.open_sink(file, &part, downloaded_size == 0).await.map_err(DownloadError::Io)?;
+    let mut writer = download_manager.buffered_writer(sink.clone(), downloaded_size).await;
 
     let download_task = async {
-        // 对于小文件，直接下载整个内容
-        let bytes = response.bytes()
-            .await
-            .map_err(|e| format!("Failed to download file: {}", e))?;
+        let mut stream = response.bytes_stream();
+        // 连接停滞超过 stall_timeout_secs 时失败，已写入的部分留给下次续传
+        loop {
+            let chunk = match tokio::time::timeout(stall_timeout, stream.next()).await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(_) => {
+                    writer.flush().await.map_err(DownloadError::Io)?;
+                    return Err(DownloadError::Network(format!(
+                        "No data received for {}s", config.stall_timeout_secs
+                    )));
+                }
+            };
+            let bytes = chunk.map_err(|e| DownloadError::Network(format!("Failed to download file: {}", e)))?;
 
-        // 写入文件
-        output_file.write_all(&bytes)
-            .await
-            .map_err(|e| format!("Failed to write file: {}", e))?;
+            // 暂停时在写入前等待
+            download_manager.wait_if_paused().await;
+
+            // 写入文件
+            match download_manager.transform() {
+                Some(transform) => {
+                    let transformed = transform.transform(&bytes)
+                        .map_err(|e| DownloadError::Other(format!("Failed to transform {}: {}", file.rfilename, e)))?;
+                    writer.write(&transformed).await
+                }
+                None => writer.write(&bytes).await,
+            }
+            .map_err(DownloadError::Io)?;
 
-        // 更新进度
-        let bytes_len = bytes.len() as u64;
-        if bytes_len > 0 {
-            download_manager.update_progress(&file.rfilename, bytes_len).await;
+            // 更新进度
+            let bytes_len = bytes.len() as u64;
+            download_manager.stats().record_bytes(&file.rfilename, bytes_len);
+            download_manager.throttle(bytes_len).await;
+            if bytes_len > 0 {
+                download_manager.update_progress(&file.rfilename, bytes_len).await;
+                if let Err(error) = download_manager.advance_callback(&file.rfilename, bytes_len) {
+                    writer.flush().await.map_err(DownloadError::Io)?;
+                    return Err(error);
+                }
+            }
+        }
+        writer.flush().await.map_err(DownloadError::Io)?;
+        sink.finalize().await.map_err(DownloadError::Io)?;
+        if download_manager.has_custom_sink() {
+            return Ok(());
         }
 
-        Ok::<_, String>(())
+        // 校验通过后才把临时文件改名为目标文件；校验失败的临时文件删除，下次从头下载
+        if let Err(error) = verify_part(file, &part, total_size, download_manager).await {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(error);
+        }
+        if stale_progress {
+            ChunkProgress::remove(path);
+        }
+        commit_part(path).await.map_err(DownloadError::Io)
     };
 
     tokio::select! {
         result = download_task => {
             result?;
             // 完成下载
+            download_manager.finish_callback(&file.rfilename)?;
             download_manager.finish_file(&file.rfilename).await;
             Ok(())
         }
         _ = shutdown.recv() => {
             download_manager.handle_interrupt(&file.rfilename).await;
-            Err("Download interrupted by user".to_string())
+            if download_manager.get_config().discard_partial_on_interrupt && !download_manager.has_custom_sink() {
+                discard_partial(path);
+            }
+            Err(DownloadError::Interrupted)
+        }
+    }
+}
+
+// 检查临时文件的大小，开启摘要校验时同时比对摘要；设置了字节流变换时内容与远端不同，不检查
+async fn verify_part(file: &FileInfo, part: &Path, total_size: u64, download_manager: &DownloadManager) -> Result<(), DownloadError> {
+    if download_manager.transform().is_some() {
+        return Ok(());
+    }
+    // 大小未知时按响应长度校验，LFS 指针也能通过，单独检查
+    if lfs::is_lfs(file) && lfs::is_pointer(part) {
+        return Err(DownloadError::Integrity(format!(
+            "{} was served as a Git LFS pointer instead of its content; the CDN redirect was not followed", file.rfilename
+        )));
+    }
+    let written = tokio::fs::metadata(part)
+        .await
+        .map(|m| m.len())
+        .map_err(|e| DownloadError::Io(format!("Failed to stat {}: {}", part.display(), e)))?;
+    if written != total_size {
+        return Err(DownloadError::Integrity(format!(
+            "{} has size {} after download, expected {}", file.rfilename, written, total_size
+        )));
+    }
+
+    let config = download_manager.get_config();
+    if !config.verify_checksums && config.checksum_algo.is_none() {
+        return Ok(());
+    }
+    if let Some((algo, expected)) = expected_checksum(file, config.checksum_algo) {
+        let digest = hash_file(part, algo).await.map_err(DownloadError::Io)?;
+        if digest != expected {
+            return Err(DownloadError::Integrity(format!(
+                "{} has checksum {} after download, expected {}", file.rfilename, digest, expected
+            )));
         }
     }
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn download_folder(
     client: Client,
-    endpoint: String,
+    config: &Config,
     model_id: String,
     base_path: PathBuf,
     name: String,
     files: Vec<FileInfo>,
     token: Option<String>,
     is_dataset: bool,
+    stats: Arc<DownloadStats>,
+    transform: Option<Arc<dyn StreamTransform>>,
+    sink: Option<Arc<dyn SinkProvider>>,
+    progress_callback: Option<Arc<dyn ProgressCallback>>,
     shutdown: crate::ShutdownHandle,
-) -> PyResult<()> {
+) -> Result<Vec<FailedFile>, DownloadError> {
     let folder_name = name.clone();
     let folder_path = base_path;
     tokio::fs::create_dir_all(&folder_path)
         .await
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create directory: {}", e)))?;
+        .map_err(|e| DownloadError::Io(format!("Failed to create directory: {}", e)))?;
 
     let mut need_download_files = Vec::new();
     let mut total_download_size = 0;
@@ -149,66 +271,100 @@ pub async fn download_folder(
     // 检查需要下载的文件
     let mut downloaded_files = 0;
     for file in &files {
-        let file_path = folder_path.join(&file.rfilename);
+        let file_path = local_path(&folder_path, &file.rfilename, &config.path_map);
         if let Some(size) = file.size {
-            let file_downloaded_size = get_downloaded_size(&file_path).await;
+            // 分块下载未完成时只计入已完成的块
+            let file_downloaded_size = match local_progress(&file_path) {
+                Some(completed) => completed,
+                None => match get_downloaded_size(&file_path).await {
+                    // 目标文件不完整时已下载的部分在临时文件中
+                    local_size if is_complete(local_size, Some(size)) => local_size,
+                    _ => part_size(&file_path),
+                },
+            };
             downloaded_size += file_downloaded_size;
-            if file_downloaded_size < size {
-                total_download_size += size - file_downloaded_size;
+            if transform.is_some() || !is_complete(file_downloaded_size, Some(size)) || has_progress(&file_path) {
+                total_download_size += size.saturating_sub(file_downloaded_size);
                 need_download_files.push(file.clone());
             } else {
                 downloaded_files += 1;
             }
+        } else {
+            // 大小未知时无法判断是否已完成，总是重新下载，由响应头确定大小
+            need_download_files.push(file.clone());
         }
     }
 
+    stats.record_skipped(downloaded_files);
+
     // 如果所有文件都已下载完成，直接返回
     if need_download_files.is_empty() {
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     println!("Found {} already downloaded files, downloading remaining {} files, total size: {} bytes",
             downloaded_files, need_download_files.len(), total_download_size);
 
-    // 检查是否所有文件都在同一个子文件夹中
-    let is_subfolder_download = if let Some(first_file) = need_download_files.first() {
-        // 检查文件路径中是否包含斜杠（表示在子文件夹中）
-        first_file.rfilename.contains('/')
-    } else {
-        false
-    };
+    // 检查是否所有待下载文件都在同一个子文件夹中
+    // 放宽过滤条件后重新运行时，新增的文件可能分布在不同子文件夹或根目录
+    let first_folder = need_download_files.first()
+        .and_then(|file| file.rfilename.split_once('/'))
+        .map(|(folder, _)| folder.to_string());
+    let is_subfolder_download = first_folder.as_ref().is_some_and(|folder| {
+        need_download_files.iter().all(|file| {
+            file.rfilename.split_once('/').is_some_and(|(f, _)| f == folder)
+        })
+    });
 
     // 创建下载管理器
     let download_manager = if is_subfolder_download {
         // 获取子文件夹名称
-        let folder_display_name = if let Some(first_file) = need_download_files.first() {
-            first_file.rfilename.split('/').next().unwrap_or(&folder_name).to_string()
-        } else {
-            folder_name.clone()
-        };
-        DownloadManager::new_folder(total_download_size + downloaded_size, folder_display_name, crate::config::Config::default())
+        let folder_display_name = first_folder.unwrap_or_else(|| folder_name.clone());
+        DownloadManager::new_folder(total_download_size + downloaded_size, folder_display_name, config.clone())
     } else {
-        DownloadManager::new_folder(total_download_size + downloaded_size, folder_name.clone(), crate::config::Config::default())
-    };
+        DownloadManager::new_folder(total_download_size + downloaded_size, folder_name.clone(), config.clone())
+    }.with_pause_signal(shutdown.subscribe_pause())
+        .with_stats(stats)
+        .with_transform(transform)
+        .with_sink(sink)
+        .with_progress_callback(progress_callback);
 
     // 设置已下载的大小
     let pb = download_manager.create_file_progress("".to_string(), total_download_size + downloaded_size).await;
     pb.inc(downloaded_size);
 
-    let download_task = async {
-        let mut tasks = Vec::new();
+    // 设置了 concurrent_small_downloads/concurrent_large_downloads 时小文件和大文件分成两个队列各自限流，
+    // 未设置的一侧使用 concurrent_downloads；都没设置时所有文件共用一个队列
+    let (small_files, large_files, small_limit, large_limit) =
+        if config.concurrent_small_downloads.is_some() || config.concurrent_large_downloads.is_some() {
+            let (large_files, small_files): (Vec<_>, Vec<_>) = need_download_files.into_iter()
+                .partition(|file| file.size.unwrap_or(0) > config.parallel_download_threshold);
+            (
+                small_files,
+                large_files,
+                config.concurrent_small_downloads.unwrap_or(config.concurrent_downloads),
+                config.concurrent_large_downloads.unwrap_or(config.concurrent_downloads),
+            )
+        } else {
+            (need_download_files, Vec::new(), config.concurrent_downloads, 1)
+        };
 
-        for file in need_download_files {
-            let file_path = folder_path.join(&file.rfilename);
+    let download_task = async {
+        // 按需逐个创建任务，每个队列同时最多 limit 个，文件数很多时内存保持有界
+        let spawn_download = |file: FileInfo| {
+            let file_path = local_path(&folder_path, &file.rfilename, &config.path_map);
             let client = client.clone();
             let token = token.clone();
-            let endpoint = endpoint.clone();
+            let endpoint = config.endpoint.clone();
             let model_id = model_id.clone();
             let download_manager = download_manager.clone();
-            let mut shutdown_rx = shutdown.subscribe();
+            let shutdown_rx = shutdown.subscribe();
 
+            let rfilename = file.rfilename.clone();
             let task = tokio::spawn(async move {
-                if file.size.unwrap_or(0) > download_manager.get_config().parallel_download_threshold {
+                if download_manager.transform().is_none()
+                && file.size.unwrap_or(0) > download_manager.get_config().parallel_download_threshold
+            {
                     download_chunked_file(
                         &client,
                         &file,
@@ -237,32 +393,63 @@ pub async fn download_folder(
                 }
             });
 
-            tasks.push(task);
-        }
+            async move {
+                let result = task.await
+                    .map_err(|e| DownloadError::Other(format!("Task failed: {}", e)))
+                    .and_then(|r| r);
+                (rfilename, result)
+            }
+        };
+        let mut results = futures::stream::select(
+            futures::stream::iter(small_files).map(&spawn_download).buffer_unordered(small_limit.max(1)),
+            futures::stream::iter(large_files).map(&spawn_download).buffer_unordered(large_limit.max(1)),
+        );
 
-        for task in tasks {
-            task.await.map_err(|e| format!("Task failed: {}", e))??;
+        // keep_going 模式下单个文件失败不会中止整个下载，而是记录下来
+        let mut failures = Vec::new();
+        while let Some((rfilename, result)) = results.next().await {
+            if result.is_ok() {
+                download_manager.stats().record_downloaded();
+            }
+            if let Err(error) = result {
+                // 进度回调出错时即使 keep_going 也中止
+                if error.is_aborted() {
+                    return Err(error);
+                }
+                // 服务器上已删除的文件只跳过并警告，不中止其余下载
+                if error.is_gone() {
+                    crate::warnings::warn(format!("Skipping {}: {}", rfilename, error));
+                    failures.push(FailedFile { rfilename, error: error.to_string(), skipped: true });
+                    continue;
+                }
+                if !config.keep_going {
+                    return Err(error);
+                }
+                println!("Failed to download {}: {}", rfilename, error);
+                failures.push(FailedFile { rfilename, error: error.to_string(), skipped: false });
+            }
         }
 
-        Ok::<_, String>(())
+        Ok::<_, DownloadError>(failures)
     };
 
+    let mut shutdown_rx = shutdown.subscribe();
     tokio::select! {
         result = download_task => {
             match result {
-                Ok(_) => {
+                Ok(failures) => {
                     download_manager.finish_folder().await;
-                    Ok(())
+                    Ok(failures)
                 },
                 Err(e) => {
                     download_manager.handle_folder_interrupt().await;
-                    Err(pyo3::exceptions::PyRuntimeError::new_err(e))
+                    Err(e)
                 }
             }
         }
-        _ = shutdown.subscribe().recv() => {
+        _ = shutdown_rx.recv() => {
             download_manager.handle_folder_interrupt().await;
-            Err(pyo3::exceptions::PyRuntimeError::new_err("Download interrupted by user"))
+            Err(DownloadError::Interrupted)
         }
     }
 }
@@ -276,4 +463,101 @@ async fn get_downloaded_size(path: &PathBuf) -> u64 {
     } else {
         0
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[tokio::test]
+    async fn widening_the_filter_fetches_only_new_files() {
+        let repo = MockRepo::new("org/widen", &[
+            ("config.json", b"{}"),
+            ("weights/a.bin", b"aaaa"),
+            ("weights/b.bin", b"bbbb"),
+            ("tokenizer/vocab.txt", b"hello"),
+        ]);
+        let server = repo.clone().serve();
+        let dir = tempfile::tempdir().unwrap();
+        let config = testing::config(&server.url());
+
+        testing::download_filtered(config.clone(), "org/widen", dir.path(), &["*.json"], &[]).await.unwrap();
+        assert_eq!(server.downloads(), ["config.json"]);
+
+        // 新增的文件分布在不同的子文件夹中，已下载的文件不再请求
+        let before = server.downloads().len();
+        let (path, summary) = testing::download_filtered(config, "org/widen", dir.path(), &["*.json", "weights/", "tokenizer/*"], &[])
+            .await
+            .unwrap();
+        let mut fetched = server.downloads().split_off(before);
+        fetched.sort();
+        assert_eq!(fetched, ["tokenizer/vocab.txt", "weights/a.bin", "weights/b.bin"]);
+        assert_eq!(summary.files_skipped, 1);
+        assert_eq!(summary.files_downloaded, 3);
+        for (name, content) in &repo.files {
+            assert_eq!(&std::fs::read(std::path::Path::new(&path).join(name)).unwrap(), content);
+        }
+    }
+
+    #[tokio::test]
+    async fn many_files_do_not_pre_spawn_tasks() {
+        use crate::testing::{MockServer, Response};
+        use std::time::{Duration, Instant};
+
+        // 每个请求都挂起，下载期间存活的任务数只取决于并发数
+        let server = MockServer::start(|_| Response::ok("x").delayed(Duration::from_secs(5)));
+        let files: Vec<FileInfo> = (0..100_000)
+            .map(|i| FileInfo { rfilename: format!("data/{:06}.json", i), size: Some(1), sha256: None, blob_id: None, last_modified: None })
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.concurrent_downloads = 4;
+        let shutdown = crate::ShutdownHandle::new();
+
+        let download = download_folder(
+            Client::new(), &config, "org/huge".to_string(), dir.path().to_path_buf(), "huge".to_string(),
+            files, None, true, Arc::new(DownloadStats::new()), None, None, None, shutdown.clone(),
+        );
+        let observe = async {
+            let started = Instant::now();
+            while server.requests().len() < 4 {
+                assert!(started.elapsed() < Duration::from_secs(10), "downloads never started");
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let alive = tokio::runtime::Handle::current().metrics().num_alive_tasks();
+            shutdown.shutdown();
+            alive
+        };
+        let (result, alive) = tokio::join!(download, observe);
+
+        assert!(result.is_err());
+        assert!(alive < 100, "{} tasks alive", alive);
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn unknown_size_file_is_downloaded() {
+        let repo = MockRepo::new("org/unsized", &[("data/a.json", b"{\"a\": 1}"), ("data/b.json", b"{}")]);
+        let server = repo.serve();
+        let files = vec![
+            FileInfo { rfilename: "data/a.json".to_string(), size: None, sha256: None, blob_id: None, last_modified: None },
+            FileInfo { rfilename: "data/b.json".to_string(), size: Some(2), sha256: None, blob_id: None, last_modified: None },
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let config = testing::config(&server.url());
+
+        let failures = download_folder(
+            Client::new(), &config, "org/unsized".to_string(), dir.path().to_path_buf(), "unsized".to_string(),
+            files, None, false, Arc::new(DownloadStats::new()), None, None, None, crate::ShutdownHandle::new(),
+        ).await.unwrap();
+
+        assert!(failures.is_empty());
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["data/a.json", "data/b.json"]);
+        assert_eq!(std::fs::read(dir.path().join("data/a.json")).unwrap(), b"{\"a\": 1}");
+        assert!(!crate::warnings::recorded().iter().any(|w| w.contains("file size is unknown")));
+    }
+}
+
@@ -33,7 +33,7 @@ impl ModelDownloader {
         
         let auth = Auth { token };
         let cache_dir = cache_dir.unwrap_or_else(|| config.local_dir_base.clone());
-        let client = Client::new();
+        let client = crate::cli::build_client(&config)?;
 
         Ok(Self {
             client,
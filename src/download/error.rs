@@ -0,0 +1,265 @@
+use crate::config::Config;
+use super::failures::is_gone_status;
+use std::fmt;
+
+// 单个文件下载失败的原因，调用方据此区分网络、磁盘、认证等问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadError {
+    // 连接失败、超时或读取响应中断
+    Network(String),
+    // 本地文件（或自定义写入目标）读写失败
+    Io(String),
+    // 401/403：token 缺失、无效或没有仓库权限
+    Auth(u16),
+    // 401/403 且 Hub 标明是 gated 仓库：需要先在仓库页面申请访问
+    Gated(u16),
+    // 其他非成功状态码
+    HttpStatus(u16),
+    // 429 且带有 Retry-After：等待给定时间后重试，不计入重试次数
+    RateLimited(std::time::Duration),
+    // permanent_statuses 中的状态码：文件已不在服务器上
+    Gone(u16),
+    // 下载内容与元数据不一致
+    Integrity(String),
+    // 用户中断
+    Interrupted,
+    // 进度回调出错等需要中止整个下载的失败，keep_going 时也不继续
+    Aborted(String),
+    // 字节流变换失败、任务异常退出等
+    Other(String),
+}
+
+impl DownloadError {
+    // 按 permanent_statuses 和认证状态码对失败响应分类
+    pub fn from_response(response: &reqwest::Response, config: &Config) -> Self {
+        let status = response.status();
+        if is_gone_status(status, config) {
+            Self::Gone(status.as_u16())
+        } else if AccessDenial::from_response(status, response.headers(), None) == AccessDenial::Gated {
+            Self::Gated(status.as_u16())
+        } else if matches!(status.as_u16(), 401 | 403) {
+            Self::Auth(status.as_u16())
+        } else {
+            Self::HttpStatus(status.as_u16())
+        }
+    }
+
+    // 网络错误和 retry_statuses 中的状态码值得重试
+    pub fn is_retryable(&self, config: &Config) -> bool {
+        match self {
+            Self::Network(_) | Self::RateLimited(_) => true,
+            Self::HttpStatus(code) => config.retry_statuses.contains(code),
+            _ => false,
+        }
+    }
+
+    // 已不存在的文件只跳过并警告，不让整个下载失败
+    pub fn is_gone(&self) -> bool {
+        matches!(self, Self::Gone(_))
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        matches!(self, Self::Aborted(_))
+    }
+}
+
+// Hub 拒绝访问仓库的原因：优先看 X-Error-Code 响应头，其次是 X-Error-Message 和响应体中的错误信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDenial {
+    // gated 仓库，当前账号尚未获得访问权限
+    Gated,
+    // token 无效或已过期
+    InvalidToken,
+    // 仓库不存在，或是当前 token 无权访问的私有仓库
+    NotFound,
+    // 其他失败状态码
+    Status(u16),
+}
+
+impl AccessDenial {
+    pub fn from_response(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: Option<&str>) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        let body_message = body
+            .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
+            .and_then(|json| json["error"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        let message = format!("{} {}", header("x-error-message"), body_message).to_ascii_lowercase();
+
+        match header("x-error-code") {
+            "GatedRepo" => return Self::Gated,
+            "RepoNotFound" => return Self::NotFound,
+            _ => {}
+        }
+        if message.contains("gated") {
+            Self::Gated
+        } else if status.as_u16() == 401 && message.contains("invalid") && (message.contains("token") || message.contains("credentials")) {
+            Self::InvalidToken
+        } else if matches!(status.as_u16(), 401 | 404) {
+            Self::NotFound
+        } else {
+            Self::Status(status.as_u16())
+        }
+    }
+
+    // 成功状态码的响应体中带有错误信息时，只能按信息内容判断原因；无法判断时返回 None
+    pub fn from_message(message: &str) -> Option<Self> {
+        let message = message.to_ascii_lowercase();
+        if message.contains("gated") {
+            Some(Self::Gated)
+        } else if message.contains("invalid") && (message.contains("token") || message.contains("credentials")) {
+            Some(Self::InvalidToken)
+        } else if message.contains("not found") || message.contains("does not exist") {
+            Some(Self::NotFound)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for AccessDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gated => write!(f, "gated"),
+            Self::InvalidToken => write!(f, "invalid token"),
+            Self::NotFound => write!(f, "not found"),
+            Self::Status(code) => write!(f, "{}", status_text(*code)),
+        }
+    }
+}
+
+fn status_text(code: u16) -> String {
+    reqwest::StatusCode::from_u16(code)
+        .map(|status| status.to_string())
+        .unwrap_or_else(|_| code.to_string())
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(message) | Self::Io(message) | Self::Integrity(message) | Self::Aborted(message) | Self::Other(message) => {
+                write!(f, "{}", message)
+            }
+            Self::Auth(code) => write!(f, "Unauthorized ({}), check your access token", status_text(*code)),
+            Self::Gated(code) => write!(f, "Repository is gated ({}), request access on its Hub page and use a token from an approved account", status_text(*code)),
+            Self::HttpStatus(code) => write!(f, "Failed to download file: {}", status_text(*code)),
+            Self::RateLimited(wait) => write!(f, "Rate limited by server, retry after {}s", wait.as_secs()),
+            Self::Gone(code) => write!(f, "File no longer exists on server ({})", status_text(*code)),
+            Self::Interrupted => write!(f, "Download interrupted by user"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<DownloadError> for pyo3::PyErr {
+    fn from(error: DownloadError) -> Self {
+        let message = error.to_string();
+        match error {
+            DownloadError::Network(_) => pyo3::exceptions::PyConnectionError::new_err(message),
+            DownloadError::Io(_) => pyo3::exceptions::PyIOError::new_err(message),
+            DownloadError::Auth(_) | DownloadError::Gated(_) => pyo3::exceptions::PyPermissionError::new_err(message),
+            DownloadError::Integrity(_) => pyo3::exceptions::PyValueError::new_err(message),
+            DownloadError::Interrupted => pyo3::exceptions::PyKeyboardInterrupt::new_err(message),
+            DownloadError::HttpStatus(_) | DownloadError::RateLimited(_) | DownloadError::Gone(_) | DownloadError::Aborted(_) | DownloadError::Other(_) => {
+                pyo3::exceptions::PyRuntimeError::new_err(message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccessDenial;
+    use crate::download::failures::FailureLog;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+
+    #[tokio::test]
+    async fn missing_file_is_skipped_without_retrying() {
+        let repo = MockRepo::new("org/stale", &[("a.bin", b"aaaa"), ("gone.bin", b"gone"), ("c.bin", b"cccc")]);
+        let server = MockServer::start(move |request| {
+            if request.method == "GET" && request.path.ends_with("/gone.bin") {
+                return Response::new(404).header("X-Error-Code", "EntryNotFound");
+            }
+            repo.respond(request).unwrap_or_else(Response::not_found)
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("failures.json");
+        let mut config = testing::config(&server.url());
+        config.failures_log = Some(log_path.to_string_lossy().to_string());
+
+        // 没有 keep_going 时也不会让整个下载失败
+        testing::download(config, "org/stale", &dir.path().join("out")).await.unwrap();
+
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["a.bin", "c.bin", "gone.bin"]);
+        assert_eq!(std::fs::read(dir.path().join("out/org/stale/a.bin")).unwrap(), b"aaaa");
+        assert_eq!(std::fs::read(dir.path().join("out/org/stale/c.bin")).unwrap(), b"cccc");
+        assert!(!dir.path().join("out/org/stale/gone.bin").exists());
+
+        let failures = FailureLog::load(&log_path).unwrap().failures;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].rfilename, "gone.bin");
+        assert!(failures[0].skipped);
+        assert!(failures[0].error.contains("no longer exists"), "{}", failures[0].error);
+    }
+
+    #[test]
+    fn access_denial_reads_headers_and_body() {
+        use reqwest::header::{HeaderMap, HeaderValue};
+        use reqwest::StatusCode;
+
+        let headers = |pairs: &[(&'static str, &str)]| {
+            HeaderMap::from_iter(pairs.iter().map(|(name, value)| (reqwest::header::HeaderName::from_static(name), HeaderValue::from_str(value).unwrap())))
+        };
+        let deny = |status: u16, pairs: &[(&'static str, &str)], body: Option<&str>| {
+            AccessDenial::from_response(StatusCode::from_u16(status).unwrap(), &headers(pairs), body)
+        };
+
+        assert_eq!(deny(403, &[("x-error-code", "GatedRepo")], None), AccessDenial::Gated);
+        assert_eq!(deny(401, &[("x-error-code", "RepoNotFound")], None), AccessDenial::NotFound);
+        assert_eq!(deny(403, &[("x-error-message", "Access to model org/m is restricted, it is a gated repo")], None), AccessDenial::Gated);
+        assert_eq!(deny(401, &[], Some(r#"{"error": "Invalid credentials in Authorization header"}"#)), AccessDenial::InvalidToken);
+        assert_eq!(deny(401, &[("x-error-message", "Invalid username or password.")], None), AccessDenial::NotFound);
+        assert_eq!(deny(404, &[], Some("not json")), AccessDenial::NotFound);
+        assert_eq!(deny(500, &[], None), AccessDenial::Status(500));
+    }
+
+    #[tokio::test]
+    async fn gated_repo_error_names_the_access_page() {
+        let server = MockServer::start(|_| Response::new(403).header("X-Error-Code", "GatedRepo"));
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = testing::download(testing::config(&server.url()), "org/gated", dir.path()).await.err().unwrap();
+        let message = testing::error_message(error);
+        assert!(message.contains("Repository org/gated is gated"), "{}", message);
+        assert!(message.contains(&format!("{}/org/gated", server.url())), "{}", message);
+    }
+
+    #[test]
+    fn error_messages_in_ok_bodies_are_classified() {
+        assert_eq!(AccessDenial::from_message("Access to model org/m is restricted and you are not in the authorized list. Visit https://hf.co/org/m to ask for access. This is a gated repo."), Some(AccessDenial::Gated));
+        assert_eq!(AccessDenial::from_message("Invalid credentials in Authorization header"), Some(AccessDenial::InvalidToken));
+        assert_eq!(AccessDenial::from_message("Repository Not Found"), Some(AccessDenial::NotFound));
+        assert_eq!(AccessDenial::from_message("Something went wrong"), None);
+    }
+
+    #[tokio::test]
+    async fn ok_response_with_an_error_body_is_mapped() {
+        let body = |error: &'static str| move |_: &testing::Request| Response::json(serde_json::json!({ "error": error }));
+        let dir = tempfile::tempdir().unwrap();
+
+        let gated = MockServer::start(body("Access to model org/m is restricted. This is a gated repo."));
+        let message = testing::error_message(testing::download(testing::config(&gated.url()), "org/m", dir.path()).await.unwrap_err());
+        assert!(message.contains("Repository org/m is gated"), "{}", message);
+
+        let invalid = MockServer::start(body("Invalid credentials in Authorization header"));
+        let message = testing::error_message(testing::download(testing::config(&invalid.url()), "org/m", dir.path()).await.unwrap_err());
+        assert!(message.contains("The access token is invalid or expired"), "{}", message);
+
+        // 无法归类的错误直接报告服务器的信息，不会当作没有文件的仓库
+        let other = MockServer::start(body("Internal error, please retry later"));
+        let message = testing::error_message(testing::download(testing::config(&other.url()), "org/m", dir.path()).await.unwrap_err());
+        assert_eq!(message, "Failed to get repo info: Internal error, please retry later");
+    }
+}
@@ -0,0 +1,153 @@
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 同一个文件两次进度事件之间的最短间隔
+const EVENT_INTERVAL: Duration = Duration::from_millis(500);
+
+// 进度输出方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressFormat {
+    // 终端进度条
+    #[default]
+    Bars,
+    // 不画进度条，向 stderr 逐行输出 JSON 事件，供其他程序解析
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bars" => Ok(Self::Bars),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown progress format '{}', expected bars or json", s)),
+        }
+    }
+}
+
+// 一行一个事件；文件夹模式下 file 为 null，表示整个文件夹的汇总进度
+#[derive(Serialize)]
+struct ProgressEvent<'a> {
+    file: Option<&'a str>,
+    downloaded: u64,
+    total: Option<u64>,
+    speed: u64,
+    done: bool,
+}
+
+// 按文件节流的 JSON 进度事件输出，默认写到 stderr
+pub struct ProgressEvents {
+    last_emitted: Mutex<HashMap<Option<String>, Instant>>,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Default for ProgressEvents {
+    fn default() -> Self {
+        Self::with_output(Box::new(std::io::stderr()))
+    }
+}
+
+impl ProgressEvents {
+    pub fn with_output(output: Box<dyn Write + Send>) -> Self {
+        Self {
+            last_emitted: Mutex::new(HashMap::new()),
+            output: Mutex::new(output),
+        }
+    }
+
+    // 距上次输出不足 EVENT_INTERVAL 时跳过；done 事件总是输出
+    pub fn emit(&self, file: Option<&str>, pb: &ProgressBar, done: bool) {
+        {
+            let mut last_emitted = self.last_emitted.lock().unwrap();
+            let now = Instant::now();
+            let key = file.map(String::from);
+            if !done && last_emitted.get(&key).is_some_and(|last| now.duration_since(*last) < EVENT_INTERVAL) {
+                return;
+            }
+            last_emitted.insert(key, now);
+        }
+
+        let event = ProgressEvent {
+            file,
+            downloaded: pb.position(),
+            total: pb.length(),
+            speed: pb.per_sec() as u64,
+            done,
+        };
+        if let Ok(line) = serde_json::to_string(&event) {
+            let _ = writeln!(self.output.lock().unwrap(), "{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::chunk::download_chunked_file;
+    use crate::download::DownloadManager;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use crate::types::FileInfo;
+    use std::sync::Arc;
+
+    // 收集写出的事件，供测试读取
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn last_event_of_a_chunked_file_is_complete() {
+        let content: Vec<u8> = (0..40_000u32).map(|i| (i % 251) as u8).collect();
+        let repo = MockRepo::new("org/events", &[("model.bin", &content)]);
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).paced(512, Duration::from_millis(25))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.progress_format = ProgressFormat::Json;
+        let captured = Captured::default();
+        let mut manager = DownloadManager::new(content.len() as u64, config.clone());
+        manager.events = Some(Arc::new(ProgressEvents::with_output(Box::new(captured.clone()))));
+        let file = FileInfo {
+            rfilename: "model.bin".to_string(),
+            size: Some(content.len() as u64),
+            sha256: None,
+            blob_id: None,
+            last_modified: None,
+        };
+        let (_shutdown, receiver) = tokio::sync::broadcast::channel(1);
+
+        download_chunked_file(
+            &reqwest::Client::new(), &file, &dir.path().join("model.bin"), 4096, config.max_retries, None,
+            &config.endpoint, "org/events", false, &manager, receiver,
+        ).await.unwrap();
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert!(events.len() >= 2, "{}", output);
+        for event in &events {
+            assert_eq!(event["file"], "model.bin");
+            assert!(event["downloaded"].as_u64().unwrap() <= content.len() as u64, "{}", event);
+        }
+        let last = events.last().unwrap();
+        assert_eq!(last["done"], true);
+        assert_eq!(last["downloaded"], last["total"]);
+        assert_eq!(last["total"], content.len() as u64);
+    }
+}
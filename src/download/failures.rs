@@ -0,0 +1,206 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedFile {
+    pub rfilename: String,
+    pub error: String,
+    // 服务器上已不存在而被跳过的文件，不会让本次下载失败
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+// permanent_statuses（默认 404/410）表示文件已不存在，重试没有意义
+pub fn is_gone_status(status: reqwest::StatusCode, config: &Config) -> bool {
+    config.permanent_statuses.contains(&status.as_u16())
+}
+
+// retry_statuses（默认 429 和 500/502/503/504）是暂时性错误，可以重试
+pub fn is_retryable_status(status: reqwest::StatusCode, config: &Config) -> bool {
+    config.retry_statuses.contains(&status.as_u16())
+}
+
+// 第 attempt 次（从 1 开始）重试前的等待时间：从 1 秒开始翻倍，最多 30 秒
+pub fn backoff_delay(attempt: usize) -> std::time::Duration {
+    let exponent = attempt.saturating_sub(1).min(5) as u32;
+    std::time::Duration::from_secs(2u64.pow(exponent).min(30))
+}
+
+// 429 响应中的 Retry-After：秒数或 HTTP 日期，无法解析时返回 None
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(std::time::SystemTime::now()).unwrap_or_default())
+}
+
+// 失败文件记录，供 --retry-failed 只重试这些文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FailureLog {
+    pub repo_id: String,
+    pub failures: Vec<FailedFile>,
+}
+
+impl FailureLog {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read failures file {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid failures file {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize failures: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write failures file {}: {}", path.display(), e))
+    }
+
+    pub fn contains(&self, rfilename: &str) -> bool {
+        self.failures.iter().any(|f| f.rfilename == rfilename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn retry_failed_downloads_only_logged_files_and_shrinks_the_log() {
+        let repo = MockRepo::new("org/flaky", &[("a.bin", b"aaaa"), ("b.bin", b"bbbb"), ("c.bin", b"cccc")]);
+        let broken = Arc::new(Mutex::new(HashSet::from(["b.bin", "c.bin"])));
+        let server = {
+            let broken = broken.clone();
+            MockServer::start(move |request| {
+                let broken = broken.lock().unwrap().iter().any(|name| request.path.ends_with(&format!("/{}", name)));
+                if broken && request.method == "GET" {
+                    return Response::new(400);
+                }
+                repo.respond(request).unwrap_or_else(Response::not_found)
+            })
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("failures.json");
+        let log = || {
+            let mut names: Vec<_> = FailureLog::load(&log_path).unwrap().failures.into_iter().map(|f| f.rfilename).collect();
+            names.sort();
+            names
+        };
+        let out = dir.path().join("out");
+
+        let mut config = testing::config(&server.url());
+        config.keep_going = true;
+        config.failures_log = Some(log_path.to_string_lossy().to_string());
+        assert!(testing::download(config, "org/flaky", &out).await.is_err());
+        assert_eq!(log(), ["b.bin", "c.bin"]);
+
+        // 只重试记录中的文件，仍然失败的留在记录里
+        broken.lock().unwrap().remove("c.bin");
+        let mut config = testing::config(&server.url());
+        config.retry_failed = Some(log_path.to_string_lossy().to_string());
+        config.keep_going = true;
+        let before = server.downloads().len();
+        assert!(testing::download(config.clone(), "org/flaky", &out).await.is_err());
+        let mut fetched = server.downloads().split_off(before);
+        fetched.sort();
+        assert_eq!(fetched, ["b.bin", "c.bin"]);
+        assert_eq!(log(), ["b.bin"]);
+
+        broken.lock().unwrap().clear();
+        let before = server.downloads().len();
+        testing::download(config, "org/flaky", &out).await.unwrap();
+        assert_eq!(server.downloads().split_off(before), ["b.bin"]);
+        assert!(log().is_empty());
+        assert_eq!(std::fs::read(out.join("org/flaky/b.bin")).unwrap(), b"bbbb");
+    }
+
+    // 每次 GET 都先返回一次 status，之后正常响应
+    fn fails_once_with(status: u16) -> MockServer {
+        let repo = MockRepo::new("org/gateway", &[("model.bin", b"weights")]);
+        let failed = Mutex::new(false);
+        MockServer::start(move |request| {
+            if request.method == "GET" && request.path.ends_with("/model.bin") && !std::mem::replace(&mut *failed.lock().unwrap(), true) {
+                return Response::new(status);
+            }
+            repo.respond(request).unwrap_or_else(Response::not_found)
+        })
+    }
+
+    #[tokio::test]
+    async fn only_configured_statuses_are_retried() {
+        let gets = |server: &MockServer| server.requests().iter().filter(|r| r.method == "GET" && r.path.ends_with("/model.bin")).count();
+
+        // 520 加入 retry_statuses 后重试一次即可完成
+        let server = fails_once_with(520);
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.retry_statuses.push(520);
+        assert!(is_retryable_status(reqwest::StatusCode::from_u16(520).unwrap(), &config));
+        let (path, _) = testing::download(config, "org/gateway", dir.path()).await.unwrap();
+        assert_eq!(std::fs::read(Path::new(&path).join("model.bin")).unwrap(), b"weights");
+        assert_eq!(gets(&server), 2);
+
+        // 不在列表中的 521 直接失败，不再请求
+        let server = fails_once_with(521);
+        let dir = tempfile::tempdir().unwrap();
+        let config = testing::config(&server.url());
+        assert!(!is_retryable_status(reqwest::StatusCode::from_u16(521).unwrap(), &config));
+        assert!(testing::download(config, "org/gateway", dir.path()).await.is_err());
+        assert_eq!(gets(&server), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_thirty_seconds() {
+        let secs: Vec<u64> = (1..=8).map(|attempt| backoff_delay(attempt).as_secs()).collect();
+        assert_eq!(secs, [1, 2, 4, 8, 16, 30, 30, 30]);
+        assert_eq!(backoff_delay(0).as_secs(), 1);
+        assert_eq!(backoff_delay(usize::MAX).as_secs(), 30);
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+        let headers = |value: &str| HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from_str(value).unwrap())]);
+        assert_eq!(retry_after(&headers("7")), Some(std::time::Duration::from_secs(7)));
+        assert_eq!(retry_after(&headers(" 0 ")), Some(std::time::Duration::ZERO));
+        let later = httpdate::fmt_http_date(std::time::SystemTime::now() + std::time::Duration::from_secs(60));
+        let wait = retry_after(&headers(&later)).unwrap();
+        assert!(wait > std::time::Duration::from_secs(55) && wait <= std::time::Duration::from_secs(60), "{:?}", wait);
+        // 已经过去的日期不需要等待
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), Some(std::time::Duration::ZERO));
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_download_waits_and_succeeds() {
+        let repo = MockRepo::new("org/limited", &[("model.bin", b"weights")]);
+        let limited = Mutex::new(2);
+        let server = MockServer::start(move |request| {
+            let mut remaining = limited.lock().unwrap();
+            if request.method == "GET" && request.path.ends_with("/model.bin") && *remaining > 0 {
+                *remaining -= 1;
+                return Response::new(429).header("Retry-After", "1");
+            }
+            repo.respond(request).unwrap_or_else(Response::not_found)
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        // 429 不计入重试次数，只重试一次也能等到成功
+        config.max_retries = 1;
+
+        let started = std::time::Instant::now();
+        let (path, _) = testing::download(config, "org/limited", dir.path()).await.unwrap();
+        assert!(started.elapsed() >= std::time::Duration::from_secs(2));
+        assert_eq!(std::fs::read(Path::new(&path).join("model.bin")).unwrap(), b"weights");
+        assert_eq!(server.downloads(), ["model.bin", "model.bin", "model.bin"]);
+    }
+}
+
@@ -1,6 +1,6 @@
 use crate::types::FileInfo;
 use crate::config::Config;
-use glob::Pattern;
+use super::ignore::{compile_patterns, matches_any};
 
 #[allow(dead_code)]
 pub fn should_download(config: &Config, file: &FileInfo) -> bool {
@@ -9,29 +9,13 @@ pub fn should_download(config: &Config, file: &FileInfo) -> bool {
         return true;
     }
 
-    // 如果设置了包含规则，文件必须匹配其中之一
-    let mut should_include = config.include_patterns.is_empty();
-    if !should_include {
-        for pattern in &config.include_patterns {
-            if let Ok(glob) = Pattern::new(pattern) {
-                if glob.matches(&file.rfilename) {
-                    should_include = true;
-                    break;
-                }
-            }
-        }
-    }
+    // 模式按 filter_mode 解析，无效的模式在加载配置时已经报错
+    let include = compile_patterns(&config.include_patterns, config.filter_mode).unwrap_or_default();
+    let exclude = compile_patterns(&config.exclude_patterns, config.filter_mode).unwrap_or_default();
 
-    // 如果文件匹配任何排除规则，则不下载
-    if should_include {
-        for pattern in &config.exclude_patterns {
-            if let Ok(glob) = Pattern::new(pattern) {
-                if glob.matches(&file.rfilename) {
-                    return false;
-                }
-            }
-        }
-    }
+    // 如果设置了包含规则，文件必须匹配其中之一
+    let should_include = include.is_empty() || matches_any(&include, &file.rfilename);
 
-    should_include
-} 
\ No newline at end of file
+    // 排除规则在包含规则之后应用：同时匹配两者的文件不下载
+    should_include && !matches_any(&exclude, &file.rfilename)
+}
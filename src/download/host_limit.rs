@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// 每个主机同时进行的请求数上限（max_connections_per_host），各主机分别计数，
+// 同时使用多个端点时每个端点都只承受自己的上限
+pub struct HostLimiter {
+    limit: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn semaphore(&self, host: &str) -> Arc<Semaphore> {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limit)))
+            .clone()
+    }
+
+    // 请求结束（包括读完响应体）前持有许可；无法解析出主机的 URL 不受限制
+    pub async fn acquire(&self, url: &str) -> Option<OwnedSemaphorePermit> {
+        let host = reqwest::Url::parse(url).ok()?.host_str()?.to_ascii_lowercase();
+        self.semaphore(&host)
+            .acquire_owned()
+            .await
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn each_host_stays_under_its_own_limit() {
+        let slow = || MockServer::start(|_| Response::ok("x").delayed(Duration::from_millis(50)));
+        let (a, b) = (slow(), slow());
+        // 两个服务器分别用 127.0.0.1 和 localhost 访问，是两个不同的主机
+        let hosts = [a.url(), b.url().replace("127.0.0.1", "localhost")];
+        let limiter = HostLimiter::new(2);
+        let client = reqwest::Client::new();
+
+        let requests = (0..16).map(|i| {
+            let url = format!("{}/file-{}", hosts[i % 2], i);
+            let (limiter, client) = (&limiter, &client);
+            async move {
+                let _permit = limiter.acquire(&url).await;
+                client.get(&url).send().await.unwrap().bytes().await.unwrap();
+            }
+        });
+        futures::future::join_all(requests).await;
+
+        assert_eq!(a.requests().len(), 8);
+        assert_eq!(b.requests().len(), 8);
+        // 各自达到上限，但互不占用对方的名额
+        assert_eq!(a.max_in_flight(), 2);
+        assert_eq!(b.max_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn chunked_download_respects_the_host_limit() {
+        let content: Vec<u8> = (0..128u8).collect();
+        let repo = MockRepo::new("org/polite", &[("model.bin", &content)]);
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).delayed(Duration::from_millis(30))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 16;
+        config.chunk_size = 16;
+        config.connections_per_download = 8;
+        config.max_connections_per_host = Some(2);
+        testing::download(config, "org/polite", dir.path()).await.unwrap();
+
+        assert_eq!(server.requests().iter().filter(|r| r.range().is_some()).count(), 8);
+        assert!(server.max_in_flight() <= 2, "{} requests in flight", server.max_in_flight());
+        assert_eq!(std::fs::read(dir.path().join("org/polite/model.bin")).unwrap(), content);
+    }
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+// 下载目录中的忽略文件，其中的 glob 模式会并入排除列表
+pub const IGNORE_FILE: &str = ".hfdignore";
+
+// 读取 `.hfdignore`，忽略空行和 `#` 注释；文件不存在时返回空列表
+pub fn load_ignore_patterns(dir: &Path) -> Result<Vec<String>, String> {
+    let path = dir.join(IGNORE_FILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    Ok(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+// include/exclude 模式的语法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterMode {
+    // shell 通配符：`*` 匹配任意字符（包括 `/`），`?` 匹配单个字符，`[...]` 匹配字符集合；
+    // 以 `/` 结尾的模式匹配该目录下的所有文件，与 huggingface_hub 的 allow/ignore_patterns 相同
+    #[default]
+    #[serde(rename = "glob")]
+    Glob,
+    // 正则表达式，在完整文件路径中搜索，需要整体匹配时使用 `^...$`
+    #[serde(rename = "regex")]
+    Regex,
+}
+
+impl FromStr for FilterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "glob" => Ok(Self::Glob),
+            "regex" => Ok(Self::Regex),
+            _ => Err(format!("Unknown filter mode '{}', expected glob or regex", s)),
+        }
+    }
+}
+
+// glob 匹配选项：`/` 不需要字面匹配，`*.json` 也匹配 `subdir/config.json`，与 huggingface_hub 使用的 fnmatch 一致
+const GLOB_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+// 编译后的 include/exclude 模式
+#[derive(Debug, Clone)]
+pub enum FilePattern {
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl FilePattern {
+    pub fn matches(&self, rfilename: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => pattern.matches_with(rfilename, GLOB_OPTIONS),
+            Self::Regex(pattern) => pattern.is_match(rfilename),
+        }
+    }
+}
+
+// `data/` 等价于 `data/*`
+fn glob_pattern(pattern: &str) -> String {
+    if pattern.ends_with('/') {
+        format!("{}*", pattern)
+    } else {
+        pattern.to_string()
+    }
+}
+
+// 编译 include/exclude 模式，任何一个无效都直接报错，避免拼错的模式悄悄匹配不到文件
+pub fn compile_patterns(patterns: &[String], mode: FilterMode) -> Result<Vec<FilePattern>, String> {
+    patterns.iter()
+        .map(|pattern| {
+            let compiled = match mode {
+                FilterMode::Glob => glob::Pattern::new(&glob_pattern(pattern))
+                    .map(FilePattern::Glob)
+                    .map_err(|e| e.to_string()),
+                FilterMode::Regex => regex::Regex::new(pattern)
+                    .map(FilePattern::Regex)
+                    .map_err(|e| e.to_string()),
+            };
+            compiled.map_err(|e| format!("Invalid {:?} pattern '{}': {}", mode, pattern, e))
+        })
+        .collect()
+}
+
+// include 模式之间取并集：匹配任意一个即可
+pub fn matches_any(patterns: &[FilePattern], rfilename: &str) -> bool {
+    patterns.iter().any(|pattern| pattern.matches(rfilename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn ignore_file_skips_comments_and_blank_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_ignore_patterns(dir.path()).unwrap().is_empty());
+
+        std::fs::write(dir.path().join(IGNORE_FILE), "# weights we never use\n*.onnx\n\n  logs/  \n#*.json\n").unwrap();
+        assert_eq!(load_ignore_patterns(dir.path()).unwrap(), ["*.onnx", "logs/"]);
+    }
+
+    #[tokio::test]
+    async fn hfdignore_excludes_files_without_flags() {
+        let server = MockRepo::new("org/ignored", &[
+            ("config.json", b"{}"),
+            ("model.onnx", b"onnx"),
+            ("logs/run.txt", b"log"),
+            ("model.safetensors", b"weights"),
+        ]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/ignored");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join(IGNORE_FILE), "# local policy\n*.onnx\nlogs/\n").unwrap();
+
+        testing::download(testing::config(&server.url()), "org/ignored", dir.path()).await.unwrap();
+
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["config.json", "model.safetensors"]);
+        assert!(!target.join("model.onnx").exists());
+        assert!(!target.join("logs").exists());
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let patterns = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let error = compile_patterns(&patterns(&["*.json", "["]), FilterMode::Glob).err().unwrap();
+        assert!(error.starts_with("Invalid Glob pattern '[':"), "{}", error);
+        let error = compile_patterns(&patterns(&["model-("]), FilterMode::Regex).err().unwrap();
+        assert!(error.starts_with("Invalid Regex pattern 'model-(':"), "{}", error);
+        assert_eq!(compile_patterns(&patterns(&["*.json", "weights/"]), FilterMode::Glob).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn invalid_include_pattern_fails_before_downloading() {
+        let server = MockRepo::new("org/typo", &[("config.json", b"{}")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = testing::download_filtered(testing::config(&server.url()), "org/typo", dir.path(), &["["], &[])
+            .await
+            .err()
+            .unwrap();
+        assert!(testing::error_message(error).contains("Invalid Glob pattern '['"));
+        assert!(server.downloads().is_empty());
+    }
+
+    #[test]
+    fn glob_and_regex_select_the_same_safetensors() {
+        let matches = |pattern: &str, mode: FilterMode, rfilename: &str| {
+            matches_any(&compile_patterns(&[pattern.to_string()], mode).unwrap(), rfilename)
+        };
+        for rfilename in ["model.safetensors", "shards/model-00001.safetensors"] {
+            assert!(matches("*.safetensors", FilterMode::Glob, rfilename));
+            assert!(matches(r".*\.safetensors$", FilterMode::Regex, rfilename));
+        }
+        for rfilename in ["model.safetensors.index.json", "model.bin"] {
+            assert!(!matches("*.safetensors", FilterMode::Glob, rfilename));
+            assert!(!matches(r".*\.safetensors$", FilterMode::Regex, rfilename));
+        }
+        // 同一个模式在两种语法下含义不同
+        assert!(!matches("*.safetensors", FilterMode::Glob, "modelXsafetensors"));
+        assert!(matches(r".*.safetensors", FilterMode::Regex, "modelXsafetensors"));
+        assert_eq!("Regex".parse::<FilterMode>().unwrap(), FilterMode::Regex);
+        assert!("fnmatch".parse::<FilterMode>().is_err());
+    }
+
+    #[test]
+    fn globs_match_across_directories() {
+        let patterns = compile_patterns(&["*.json".to_string()], FilterMode::Glob).unwrap();
+        assert!(matches_any(&patterns, "config.json"));
+        assert!(matches_any(&patterns, "subdir/config.json"));
+        assert!(matches_any(&patterns, "a/b/.hidden.json"));
+        assert!(!matches_any(&patterns, "config.JSON"));
+        // 以 / 结尾的模式匹配整个目录
+        let dirs = compile_patterns(&["logs/".to_string()], FilterMode::Glob).unwrap();
+        assert!(matches_any(&dirs, "logs/run/1.txt"));
+        assert!(!matches_any(&dirs, "other/logs.txt"));
+    }
+
+    #[tokio::test]
+    async fn includes_are_a_union_and_excludes_win() {
+        let server = MockRepo::new("org/filtered", &[
+            ("config.json", b"{}"),
+            ("onnx/config.json", b"{}"),
+            ("model.safetensors", b"weights"),
+            ("onnx/model.onnx", b"onnx"),
+            ("README.md", b"readme"),
+        ]).serve();
+        let dir = tempfile::tempdir().unwrap();
+
+        // 匹配任意一个 include 即下载；同时匹配 exclude 的不下载
+        testing::download_filtered(testing::config(&server.url()), "org/filtered", dir.path(), &["*.json", "*.safetensors"], &["onnx/"])
+            .await
+            .unwrap();
+        let mut downloaded = server.downloads();
+        downloaded.sort();
+        assert_eq!(downloaded, ["config.json", "model.safetensors"]);
+    }
+}
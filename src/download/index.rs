@@ -0,0 +1,111 @@
+use super::plan::DownloadPlan;
+use super::repo_cache::unix_now;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+// 仓库和文件元数据的 SQLite 索引，同一个数据库可以收录多个仓库
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS repos (
+    repo_id TEXT NOT NULL,
+    repo_type TEXT NOT NULL,
+    revision TEXT NOT NULL,
+    commit_sha TEXT,
+    file_count INTEGER NOT NULL,
+    total_size INTEGER NOT NULL,
+    indexed_at INTEGER NOT NULL,
+    PRIMARY KEY (repo_id, repo_type, revision)
+);
+CREATE TABLE IF NOT EXISTS files (
+    repo_id TEXT NOT NULL,
+    repo_type TEXT NOT NULL,
+    revision TEXT NOT NULL,
+    path TEXT NOT NULL,
+    size INTEGER,
+    sha256 TEXT,
+    PRIMARY KEY (repo_id, repo_type, revision, path)
+);
+";
+
+// 把解析出的文件列表写入 db；同一仓库和 revision 重新索引时替换原有的行，返回写入的文件数。
+// 所有写入在一个事务中完成，中途失败不会留下只写了一部分的索引
+pub fn write_index(db: &Path, plan: &DownloadPlan, revision: Option<&str>) -> Result<usize, String> {
+    let repo_type = if plan.is_dataset { "dataset" } else { "model" };
+    let revision = revision.unwrap_or("main");
+    let error = |e: rusqlite::Error| format!("Failed to write index {}: {}", db.display(), e);
+
+    let mut conn = Connection::open(db).map_err(error)?;
+    conn.execute_batch(SCHEMA).map_err(error)?;
+    let tx = conn.transaction().map_err(error)?;
+    let key = params![plan.repo_id, repo_type, revision];
+    tx.execute("DELETE FROM files WHERE repo_id = ?1 AND repo_type = ?2 AND revision = ?3", key).map_err(error)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO repos VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![plan.repo_id, repo_type, revision, plan.commit, plan.files.len() as i64, plan.total_bytes as i64, unix_now() as i64],
+    ).map_err(error)?;
+    {
+        let mut insert = tx.prepare("INSERT INTO files VALUES (?1, ?2, ?3, ?4, ?5, ?6)").map_err(error)?;
+        for f in &plan.files {
+            insert.execute(params![
+                plan.repo_id,
+                repo_type,
+                revision,
+                f.file.rfilename,
+                f.file.size.map(|size| size as i64),
+                f.file.sha256,
+            ]).map_err(error)?;
+        }
+    }
+    tx.commit().map_err(error)?;
+    Ok(plan.files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    type FileRow = (String, String, String, String, Option<i64>, Option<String>);
+
+    fn file_rows(db: &Path) -> Vec<FileRow> {
+        let conn = Connection::open(db).unwrap();
+        let mut query = conn.prepare("SELECT * FROM files ORDER BY path").unwrap();
+        let rows = query.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+            .unwrap()
+            .collect::<Result<Vec<FileRow>, _>>()
+            .unwrap();
+        rows
+    }
+
+    #[tokio::test]
+    async fn indexed_repo_has_a_row_per_file() {
+        let repo = MockRepo::new("org/indexed", &[("config.json", b"{}"), ("model.bin", b"0123456789")]);
+        let info = repo.info();
+        let server = repo.serve();
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("catalog.sqlite");
+        let config = testing::config(&server.url());
+        let client = crate::cli::build_client(&config).unwrap();
+        let plan = crate::cli::plan_download(
+            &client, &config, "org/indexed", Some(dir.path().join("out").to_string_lossy().to_string()),
+            None, None, &crate::types::Auth { token: None },
+        ).await.unwrap();
+
+        assert_eq!(write_index(&db, &plan, None).unwrap(), 2);
+        // 重新索引时替换原有的行
+        assert_eq!(write_index(&db, &plan, None).unwrap(), 2);
+        assert!(server.downloads().is_empty());
+
+        let sha256 = |i: usize| info["siblings"][i]["lfs"]["sha256"].as_str().map(str::to_string);
+        let row = |path: &str, size: i64, sha256| ("org/indexed".to_string(), "model".to_string(), "main".to_string(), path.to_string(), Some(size), sha256);
+        assert_eq!(file_rows(&db), [row("config.json", 2, sha256(0)), row("model.bin", 10, sha256(1))]);
+
+        let conn = Connection::open(&db).unwrap();
+        let (commit, file_count, total_size): (Option<String>, i64, i64) = conn
+            .query_row("SELECT commit_sha, file_count, total_size FROM repos WHERE repo_id = 'org/indexed'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(commit.as_deref(), info["sha"].as_str());
+        assert_eq!((file_count, total_size), (2, 12));
+    }
+}
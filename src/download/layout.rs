@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// 把仓库中匹配 from 的文件放到 to 指定的本地位置。
+// to 中可以使用 {path}（仓库内完整路径）、{dir}（所在目录）和 {name}（文件名），
+// 相对路径以下载目录为基准
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
+}
+
+impl FromStr for PathMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s.split_once('=')
+            .ok_or_else(|| format!("Invalid path mapping '{}', expected GLOB=TEMPLATE", s))?;
+        glob::Pattern::new(from)
+            .map_err(|e| format!("Invalid path mapping pattern '{}': {}", from, e))?;
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+// 计算文件的本地路径：按顺序使用第一条匹配的映射，没有匹配时使用默认布局
+pub fn local_path(base_path: &Path, rfilename: &str, path_map: &[PathMapping]) -> PathBuf {
+    let mapping = path_map.iter().find(|m| {
+        glob::Pattern::new(&m.from)
+            .map(|p| p.matches(rfilename))
+            .unwrap_or(false)
+    });
+
+    match mapping {
+        Some(mapping) => {
+            let (dir, name) = rfilename.rsplit_once('/').unwrap_or(("", rfilename));
+            let target = mapping.to
+                .replace("{path}", rfilename)
+                .replace("{dir}", dir)
+                .replace("{name}", name);
+            base_path.join(shellexpand::tilde(&target).into_owned())
+        }
+        None => base_path.join(rfilename),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn first_matching_mapping_wins() {
+        let path_map: Vec<PathMapping> = ["tokenizer*.json=tokenizers/{name}", "*.json=meta/{path}", "onnx/*=exported/{dir}-{name}"]
+            .into_iter()
+            .map(|m| m.parse().unwrap())
+            .collect();
+        let base = Path::new("/data/model");
+
+        assert_eq!(local_path(base, "tokenizer.json", &path_map), base.join("tokenizers/tokenizer.json"));
+        assert_eq!(local_path(base, "config.json", &path_map), base.join("meta/config.json"));
+        assert_eq!(local_path(base, "onnx/model.onnx", &path_map), base.join("exported/onnx-model.onnx"));
+        assert_eq!(local_path(base, "model.safetensors", &path_map), base.join("model.safetensors"));
+        assert!("no-separator".parse::<PathMapping>().is_err());
+    }
+
+    #[tokio::test]
+    async fn mapped_file_lands_at_the_remapped_path() {
+        let server = MockRepo::new("org/mapped", &[("tokenizer.json", b"tok"), ("config.json", b"{}")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let custom = dir.path().join("shared/tokenizer.json");
+        let mut config = testing::config(&server.url());
+        config.path_map = vec![format!("tokenizer.json={}", custom.display()).parse().unwrap()];
+        testing::download(config, "org/mapped", &dir.path().join("out")).await.unwrap();
+
+        assert_eq!(std::fs::read(&custom).unwrap(), b"tok");
+        assert!(!dir.path().join("out/org/mapped/tokenizer.json").exists());
+        assert_eq!(std::fs::read(dir.path().join("out/org/mapped/config.json")).unwrap(), b"{}");
+    }
+}
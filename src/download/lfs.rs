@@ -0,0 +1,26 @@
+use crate::types::FileInfo;
+use std::io::Read;
+use std::path::Path;
+
+// Git LFS 指针文件的开头；/resolve/ 对 LFS 文件 302 重定向到 CDN，没有跟随重定向时拿到的就是指针
+const POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+// 指针文件通常约 130 字节，超过这个大小的不可能是指针
+const MAX_POINTER_SIZE: u64 = 1024;
+
+// siblings 中带有 lfs 字段的文件
+pub fn is_lfs(file: &FileInfo) -> bool {
+    file.sha256.is_some()
+}
+
+// 本地文件内容是 LFS 指针而不是文件本身；文件不存在或读取失败时返回 false
+pub fn is_pointer(path: &Path) -> bool {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
+    };
+    if file.metadata().map_or(true, |m| m.len() > MAX_POINTER_SIZE) {
+        return false;
+    }
+    let mut head = [0u8; POINTER_PREFIX.len()];
+    file.read_exact(&mut head).is_ok() && head == POINTER_PREFIX
+}
@@ -0,0 +1,119 @@
+use crate::types::FileInfo;
+use super::layout::{local_path, PathMapping};
+use super::progress::part_path;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+// 下载后统一文本文件的换行符
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lf" => Ok(Self::Lf),
+            "crlf" => Ok(Self::Crlf),
+            _ => Err(format!("Unknown line ending '{}', expected lf or crlf", s)),
+        }
+    }
+}
+
+// 按扩展名认定的文本文件；内容不是 UTF-8 或含有 NUL 的仍按二进制跳过
+const TEXT_EXTENSIONS: &[&str] = &[
+    "json", "jsonl", "txt", "md", "yaml", "yml", "toml", "cfg", "ini", "py", "csv", "tsv", "xml", "html", "jinja",
+];
+
+fn is_text_file(rfilename: &str) -> bool {
+    Path::new(rfilename)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// 转换后的内容，已经是目标换行符或看起来是二进制时返回 None
+fn normalize(content: &[u8], ending: LineEnding) -> Option<Vec<u8>> {
+    if content.contains(&0) {
+        return None;
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let lf = text.replace("\r\n", "\n");
+    let normalized = match ending {
+        LineEnding::Lf => lf,
+        LineEnding::Crlf => lf.replace('\n', "\r\n"),
+    };
+    (normalized != text).then(|| normalized.into_bytes())
+}
+
+// 转换下载目录中的文本文件，先写临时文件再改名替换原文件；返回被改写的文件，
+// 调用方把它们记入 TRANSFORMED_RECORD，下次运行时不会因为大小或摘要不同而重新下载
+pub async fn normalize_files(
+    base_path: &Path,
+    files: &[FileInfo],
+    path_map: &[PathMapping],
+    ending: LineEnding,
+) -> Result<Vec<FileInfo>, String> {
+    let mut changed = Vec::new();
+    for file in files.iter().filter(|file| is_text_file(&file.rfilename)) {
+        let path = local_path(base_path, &file.rfilename, path_map);
+        let Ok(content) = tokio::fs::read(&path).await else {
+            continue;
+        };
+        let Some(normalized) = normalize(&content, ending) else {
+            continue;
+        };
+
+        let temp = part_path(&path);
+        tokio::fs::write(&temp, normalized)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+        tokio::fs::rename(&temp, &path)
+            .await
+            .map_err(|e| format!("Failed to move {} to {}: {}", temp.display(), path.display(), e))?;
+        changed.push(file.clone());
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn normalize_skips_binary_and_unchanged_content() {
+        assert_eq!(normalize(b"a\r\nb\r\n", LineEnding::Lf).unwrap(), b"a\nb\n");
+        assert_eq!(normalize(b"a\nb\r\n", LineEnding::Crlf).unwrap(), b"a\r\nb\r\n");
+        assert_eq!(normalize(b"a\nb\n", LineEnding::Lf), None);
+        assert_eq!(normalize(b"a\r\n\0b", LineEnding::Lf), None);
+        assert_eq!(normalize(b"a\r\n\xff", LineEnding::Lf), None);
+        assert!(is_text_file("sub/config.JSON"));
+        assert!(!is_text_file("model.safetensors"));
+    }
+
+    #[tokio::test]
+    async fn crlf_config_is_normalized_once() {
+        let crlf = b"{\r\n  \"a\": 1\r\n}\r\n";
+        let binary = b"\r\n\0\r\n";
+        let server = MockRepo::new("org/crlf", &[("config.json", crlf), ("model.bin", binary)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.normalize_line_endings = Some(LineEnding::Lf);
+
+        testing::download(config.clone(), "org/crlf", dir.path()).await.unwrap();
+        let config_path = dir.path().join("org/crlf/config.json");
+        assert_eq!(std::fs::read(&config_path).unwrap(), b"{\n  \"a\": 1\n}\n");
+        assert_eq!(std::fs::read(dir.path().join("org/crlf/model.bin")).unwrap(), binary);
+
+        // 再次运行时不因大小与远端不同而重新下载，内容保持不变
+        testing::download(config, "org/crlf", dir.path()).await.unwrap();
+        assert_eq!(server.downloads().len(), 2);
+        assert_eq!(std::fs::read(&config_path).unwrap(), b"{\n  \"a\": 1\n}\n");
+    }
+}
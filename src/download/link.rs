@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// 往返延迟达到该值时，分块并行下载才能明显提升速度
+const CHUNKED_MIN_RTT: Duration = Duration::from_millis(30);
+const RTT_PROBES: usize = 3;
+
+// 选择分块下载还是单流下载的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMode {
+    // 超过 parallel_download_threshold 的文件分块下载
+    #[default]
+    Threshold,
+    // 按本次测得的链路延迟决定：高延迟分块，低延迟（如局域网镜像）单流
+    Auto,
+}
+
+impl FromStr for TransferMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "threshold" => Ok(Self::Threshold),
+            "auto" => Ok(Self::Auto),
+            _ => Err(format!("Unknown transfer mode '{}', expected threshold or auto", s)),
+        }
+    }
+}
+
+// 多次 HEAD 请求取最小耗时作为往返延迟，全部失败时返回 None
+pub async fn measure_rtt(client: &reqwest::Client, url: &str, token: Option<&str>) -> Option<Duration> {
+    let mut best: Option<Duration> = None;
+    for _ in 0..RTT_PROBES {
+        let mut request = client.head(url).timeout(Duration::from_secs(10));
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let start = Instant::now();
+        if request.send().await.is_ok() {
+            let rtt = start.elapsed();
+            best = Some(best.map_or(rtt, |best| best.min(rtt)));
+        }
+    }
+    best
+}
+
+// 高延迟链路上单连接吞吐受限于窗口大小，分块并行更快；低延迟时单流开销更小
+pub fn prefer_chunked(rtt: Duration) -> bool {
+    rtt >= CHUNKED_MIN_RTT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+
+    // 所有响应都延迟 latency 的服务器
+    fn server_with_latency(repo: MockRepo, latency: Duration) -> MockServer {
+        MockServer::start(move |request| repo.respond(request).unwrap_or_else(Response::not_found).delayed(latency))
+    }
+
+    async fn ranged_downloads(latency: Duration) -> usize {
+        let content: Vec<u8> = (0..64u8).collect();
+        let server = server_with_latency(MockRepo::new("org/link", &[("model.bin", &content)]), latency);
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.transfer_mode = TransferMode::Auto;
+        config.parallel_download_threshold = 16;
+        config.chunk_size = 16;
+        testing::download(config, "org/link", dir.path()).await.unwrap();
+
+        assert_eq!(std::fs::read(dir.path().join("org/link/model.bin")).unwrap(), content);
+        server.requests().iter().filter(|r| r.method == "GET" && r.range().is_some()).count()
+    }
+
+    #[tokio::test]
+    async fn measures_rtt_from_head_requests() {
+        let server = MockServer::start(|_| Response::new(200).delayed(Duration::from_millis(40)));
+        let rtt = measure_rtt(&reqwest::Client::new(), &server.url(), None).await.unwrap();
+        assert!(rtt >= Duration::from_millis(40), "{:?}", rtt);
+        assert_eq!(server.requests().len(), RTT_PROBES);
+        assert!(prefer_chunked(rtt));
+        assert!(!prefer_chunked(Duration::from_millis(1)));
+    }
+
+    #[tokio::test]
+    async fn high_latency_selects_chunking() {
+        assert_eq!(ranged_downloads(Duration::from_millis(50)).await, 4);
+    }
+
+    #[tokio::test]
+    async fn low_latency_selects_single_stream() {
+        assert_eq!(ranged_downloads(Duration::ZERO).await, 0);
+    }
+}
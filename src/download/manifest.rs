@@ -0,0 +1,179 @@
+use crate::types::FileInfo;
+use super::callback::{report_phase, Phase, ProgressCallback};
+use super::checksum::{sha256_file, verify_progress};
+use super::layout::{local_path, PathMapping};
+use std::path::Path;
+
+// 允许列表中的一项：`path sha256 size`
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+pub fn load_manifest(path: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+
+    let mut entries = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(format!(
+                "Invalid manifest line {}: expected 'path sha256 size', got '{}'",
+                line_no + 1, line
+            ));
+        }
+
+        let sha256 = parts[1].to_ascii_lowercase();
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid sha256 on manifest line {}: '{}'", line_no + 1, parts[1]));
+        }
+        let size = parts[2].parse::<u64>()
+            .map_err(|_| format!("Invalid size on manifest line {}: '{}'", line_no + 1, parts[2]))?;
+
+        entries.push(ManifestEntry {
+            path: parts[0].to_string(),
+            sha256,
+            size,
+        });
+    }
+
+    Ok(entries)
+}
+
+// 只保留允许列表中的文件；文件缺失或服务器大小与列表不符时直接报错
+pub fn select_files(files: &[FileInfo], entries: &[ManifestEntry]) -> Result<Vec<FileInfo>, String> {
+    let mut selected = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let file = files.iter()
+            .find(|f| f.rfilename == entry.path)
+            .ok_or_else(|| format!("Manifest file {} not found in repository", entry.path))?;
+
+        if let Some(size) = file.size {
+            if size != entry.size {
+                return Err(format!(
+                    "Server file {} differs from manifest: size {} != expected {}",
+                    entry.path, size, entry.size
+                ));
+            }
+        }
+
+        selected.push(FileInfo {
+            rfilename: entry.path.clone(),
+            size: Some(entry.size),
+            sha256: Some(entry.sha256.clone()),
+            blob_id: file.blob_id.clone(),
+            last_modified: file.last_modified.clone(),
+        });
+    }
+    Ok(selected)
+}
+
+// 下载完成后按允许列表校验大小与 SHA256，不符的文件会被删除以免被当作已完成
+pub async fn verify_files(
+    base_path: &Path,
+    entries: &[ManifestEntry],
+    path_map: &[PathMapping],
+    callback: Option<&dyn ProgressCallback>,
+) -> Result<(), String> {
+    let mut mismatches = Vec::new();
+    let pb = verify_progress(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        pb.set_message(format!("Verifying {}", entry.path));
+        let path = local_path(base_path, &entry.path, path_map);
+        let size = tokio::fs::metadata(&path)
+            .await
+            .map(|m| m.len())
+            .map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+
+        if size != entry.size {
+            mismatches.push(format!("{}: size {} != expected {}", entry.path, size, entry.size));
+            let _ = tokio::fs::remove_file(&path).await;
+        } else {
+            let sha256 = sha256_file(&path).await?;
+            if sha256 != entry.sha256 {
+                mismatches.push(format!("{}: sha256 {} != expected {}", entry.path, sha256, entry.sha256));
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+        pb.inc(1);
+        report_phase(callback, Phase::Verifying, &entry.path, i + 1, entries.len())?;
+    }
+    pb.finish_with_message("✓ Verification completed");
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Downloaded files do not match manifest:\n  {}",
+            mismatches.join("\n  ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    fn write_manifest(dir: &Path, lines: &[String]) -> String {
+        let path = dir.join("allowlist.txt");
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(dir.path(), &["config.json abc 10".to_string()]);
+        let error = load_manifest(Path::new(&path)).unwrap_err();
+        assert!(error.contains("Invalid sha256 on manifest line 1"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_server_size_that_differs_from_manifest() {
+        let files = vec![FileInfo {
+            rfilename: "model.bin".to_string(),
+            size: Some(100),
+            sha256: None,
+            blob_id: None,
+            last_modified: None,
+        }];
+        let entries = vec![ManifestEntry { path: "model.bin".to_string(), sha256: "0".repeat(64), size: 99 }];
+        let error = select_files(&files, &entries).unwrap_err();
+        assert!(error.contains("size 100 != expected 99"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_download_whose_hash_differs_from_manifest() {
+        let repo = MockRepo::new("org/model", &[("config.json", b"{\"a\": 1}"), ("weights.bin", b"server weights")]);
+        let server = repo.clone().serve();
+        let dir = tempfile::tempdir().unwrap();
+        // 大小与服务器一致，摘要不同
+        let manifest = write_manifest(dir.path(), &[
+            format!("config.json {} {}", testing::sha256_hex(repo.content("config.json")), repo.content("config.json").len()),
+            format!("weights.bin {} {}", testing::sha256_hex(b"expected weights"), repo.content("weights.bin").len()),
+        ]);
+
+        let mut config = testing::config(&server.url());
+        config.from_manifest = Some(manifest);
+        let out = dir.path().join("out");
+        let error = testing::download(config, "org/model", &out).await.unwrap_err();
+
+        let message = testing::error_message(error);
+        assert!(message.contains("do not match manifest"), "{}", message);
+        assert!(message.contains("weights.bin: sha256"), "{}", message);
+        assert!(!message.contains("config.json"), "{}", message);
+        // 不符的文件被删除，不会被下次运行当作已完成
+        let target = out.join("org/model");
+        assert!(!target.join("weights.bin").exists());
+        assert!(target.join("config.json").exists());
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// 预算按 KB 计数，单次申请的许可数不能超过 u32::MAX
+const UNIT: usize = 1024;
+// 预算紧张时每个连接的缓冲区不小于这个值
+pub const MIN_BUFFER_SIZE: usize = 64 * 1024;
+
+// 所有连接写入缓冲区共享的内存预算（max_buffer_memory）。
+// 剩余预算不足 buffer_size 时新连接分到更小的缓冲区，连最小缓冲区都分不到时等待其他连接释放
+pub struct BufferBudget {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+}
+
+// 一个连接占用的预算，随缓冲区一起释放
+pub struct BufferReservation {
+    pub size: usize,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl BufferBudget {
+    pub fn new(limit: u64) -> Self {
+        let units = (limit / UNIT as u64).clamp(1, u32::MAX as u64) as usize;
+        Self {
+            semaphore: Arc::new(Semaphore::new(units)),
+            limit: units * UNIT,
+        }
+    }
+
+    pub async fn reserve(&self, wanted: usize) -> BufferReservation {
+        let available = self.semaphore.available_permits() * UNIT;
+        let size = wanted.min(available)
+            .max(MIN_BUFFER_SIZE)
+            .min(self.limit);
+        let units = size.div_ceil(UNIT).max(1);
+        let permit = self.semaphore.clone()
+            .acquire_many_owned(units as u32)
+            .await
+            .expect("buffer budget semaphore is never closed");
+        BufferReservation {
+            size: units * UNIT,
+            _permit: permit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn many_connections_stay_under_the_cap() {
+        const LIMIT: usize = 512 * 1024;
+        let budget = BufferBudget::new(LIMIT as u64);
+        let in_use = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        // 32 个连接各想要 8MB 的缓冲区
+        let connections = (0..32).map(|_| async {
+            let reservation = budget.reserve(8 * 1024 * 1024).await;
+            let total = in_use.fetch_add(reservation.size, Ordering::SeqCst) + reservation.size;
+            peak.fetch_max(total, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            in_use.fetch_sub(reservation.size, Ordering::SeqCst);
+            reservation.size
+        });
+        let sizes = futures::future::join_all(connections).await;
+
+        assert!(peak.load(Ordering::SeqCst) <= LIMIT, "peak {}", peak.load(Ordering::SeqCst));
+        // 第一个连接拿到全部预算，之后预算紧张时缩小到最小缓冲区
+        assert_eq!(sizes[0], LIMIT);
+        assert!(sizes.iter().all(|&size| (MIN_BUFFER_SIZE..=LIMIT).contains(&size)));
+        assert_eq!(in_use.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn chunked_download_completes_within_a_small_budget() {
+        use crate::testing::{self, MockRepo};
+
+        let content: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+        let server = MockRepo::new("org/budget", &[("model.bin", &content)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 64 * 1024;
+        config.chunk_size = 32 * 1024;
+        config.max_buffer_memory = Some(MIN_BUFFER_SIZE as u64 * 2);
+        testing::download(config, "org/budget", dir.path()).await.unwrap();
+
+        assert_eq!(std::fs::read(dir.path().join("org/budget/model.bin")).unwrap(), content);
+    }
+}
@@ -1,14 +1,53 @@
 use std::sync::Arc;
-use tokio::sync::Semaphore;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use std::collections::{HashMap, VecDeque};
+use tokio::sync::{watch, Semaphore};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::{HashMap, HashSet, VecDeque};
 use tokio::sync::Mutex;
 use std::time::Duration;
 use crate::config::Config;
+use callback::{ProgressCallback, ProgressReporter};
+use error::DownloadError;
+use events::{ProgressEvents, ProgressFormat};
+use host_limit::HostLimiter;
+use memory::BufferBudget;
+use rate_limit::RateLimiter;
+use stats::DownloadStats;
+use sink::{BufferedWriter, DownloadSink, LocalFileSink, SinkProvider};
+use transform::StreamTransform;
 
+pub mod archive;
+pub mod budget;
+pub mod cache;
+pub mod callback;
+pub mod card;
+pub mod checksum;
 pub mod chunk;
+pub mod concat;
+pub mod dedup;
+pub mod disk;
+pub mod error;
+pub mod events;
+pub mod failures;
 pub mod file;
+pub mod host_limit;
+pub mod ignore;
+pub mod index;
+pub mod layout;
+pub mod lfs;
+pub mod line_endings;
+pub mod link;
+pub mod manifest;
+pub mod memory;
+pub mod plan;
+pub mod rate_limit;
+pub mod prefer;
+pub mod progress;
 pub mod repo;
+pub mod repo_cache;
+pub mod resolver;
+pub mod sink;
+pub mod stats;
+pub mod transform;
 pub mod download_task;
 
 #[derive(Clone)]
@@ -25,15 +64,50 @@ pub struct DownloadManager {
     file_progress: Arc<Mutex<HashMap<String, Arc<ProgressBar>>>>,
     download_queue: Arc<Mutex<VecDeque<DownloadTask>>>,
     active_downloads: Arc<Mutex<HashMap<String, DownloadTask>>>,
+    #[allow(dead_code)]
     semaphore: Arc<Semaphore>,
     config: Arc<Config>,
     is_folder: bool,  // 是否是文件夹下载
     folder_progress: Arc<Mutex<Option<Arc<ProgressBar>>>>,  // 文件夹总进度条
+    pause: watch::Receiver<bool>,  // 暂停信号，为 true 时写入循环等待
+    hidden_files: Arc<Mutex<HashSet<String>>>,  // 超出显示上限、只计入汇总进度条的文件
+    overflow_progress: Arc<Mutex<Option<Arc<ProgressBar>>>>,  // 超出上限文件的汇总进度条
+    stats: Arc<DownloadStats>,  // 本次运行的吞吐与重试统计
+    transform: Option<Arc<dyn StreamTransform>>,  // 单流下载写入前的字节流变换
+    rate_limiter: Option<Arc<RateLimiter>>,  // max_download_speed 对应的全局限速
+    buffer_budget: Option<Arc<BufferBudget>>,  // max_buffer_memory 对应的写入缓冲区预算
+    host_limiter: Option<Arc<HostLimiter>>,  // max_connections_per_host 对应的按主机并发限制
+    events: Option<Arc<ProgressEvents>>,  // progress_format 为 json 时输出的进度事件
+    sink: Option<Arc<dyn SinkProvider>>,  // 自定义写入目标，未设置时写入本地文件
+    reporter: Option<Arc<ProgressReporter>>,  // 调用方传入的进度回调
+    total_progress: Arc<ProgressBar>,  // 非文件夹模式下所有文件的汇总进度条，第二个文件开始时才显示
+    total_files: Arc<Mutex<usize>>,  // 汇总进度条中已计入的文件数
+}
+
+// 进度条的刷新间隔，同时用于 steady tick 和终端重绘频率
+fn refresh_interval(config: &Config) -> Duration {
+    Duration::from_millis(config.progress_refresh_ms.max(1))
+}
+
+// 输出 JSON 事件时进度条照常记录进度，但不绘制到终端
+fn new_multi_progress(config: &Config) -> MultiProgress {
+    match config.progress_format {
+        ProgressFormat::Bars => {
+            // indicatif 默认最多每秒重绘 20 次
+            let hz = (1000 / config.progress_refresh_ms.max(1)).clamp(1, 20) as u8;
+            MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(hz))
+        }
+        ProgressFormat::Json => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+    }
 }
 
 impl DownloadManager {
     pub fn new(_total_size: u64, config: Config) -> Self {
-        let multi_progress = Arc::new(MultiProgress::new());
+        let multi_progress = Arc::new(new_multi_progress(&config));
+        let rate_limiter = config.max_download_speed.map(|speed| Arc::new(RateLimiter::new(speed)));
+        let buffer_budget = config.max_buffer_memory.map(|limit| Arc::new(BufferBudget::new(limit)));
+        let host_limiter = config.max_connections_per_host.map(|limit| Arc::new(HostLimiter::new(limit)));
+        let events = (config.progress_format == ProgressFormat::Json).then(|| Arc::new(ProgressEvents::default()));
         
         Self {
             multi_progress,
@@ -44,23 +118,41 @@ impl DownloadManager {
             config: Arc::new(config),
             is_folder: false,
             folder_progress: Arc::new(Mutex::new(None)),
+            pause: watch::channel(false).1,
+            hidden_files: Arc::new(Mutex::new(HashSet::new())),
+            overflow_progress: Arc::new(Mutex::new(None)),
+            stats: Arc::new(DownloadStats::new()),
+            transform: None,
+            rate_limiter,
+            buffer_budget,
+            host_limiter,
+            events,
+            sink: None,
+            reporter: None,
+            total_progress: Arc::new(ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::hidden())),
+            total_files: Arc::new(Mutex::new(0)),
         }
     }
 
     pub fn new_folder(total_size: u64, folder_name: String, config: Config) -> Self {
-        let multi_progress = Arc::new(MultiProgress::new());
+        let multi_progress = Arc::new(new_multi_progress(&config));
         let pb = Arc::new(multi_progress.add(ProgressBar::new(total_size)));
         pb.set_style(ProgressStyle::default_bar()
             .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
             .unwrap()
             .progress_chars("#>-"));
         pb.set_message(format!("Downloading folder {}", folder_name));
-        pb.enable_steady_tick(Duration::from_millis(100));
+        pb.enable_steady_tick(refresh_interval(&config));
         
         // 如果是断点续传，设置已下载的大小
         if total_size > 0 {
             pb.set_position(0);
         }
+
+        let rate_limiter = config.max_download_speed.map(|speed| Arc::new(RateLimiter::new(speed)));
+        let buffer_budget = config.max_buffer_memory.map(|limit| Arc::new(BufferBudget::new(limit)));
+        let host_limiter = config.max_connections_per_host.map(|limit| Arc::new(HostLimiter::new(limit)));
+        let events = (config.progress_format == ProgressFormat::Json).then(|| Arc::new(ProgressEvents::default()));
         
         Self {
             multi_progress,
@@ -71,9 +163,129 @@ impl DownloadManager {
             config: Arc::new(config),
             is_folder: true,
             folder_progress: Arc::new(Mutex::new(Some(pb))),
+            pause: watch::channel(false).1,
+            hidden_files: Arc::new(Mutex::new(HashSet::new())),
+            overflow_progress: Arc::new(Mutex::new(None)),
+            stats: Arc::new(DownloadStats::new()),
+            transform: None,
+            rate_limiter,
+            buffer_budget,
+            host_limiter,
+            events,
+            sink: None,
+            reporter: None,
+            total_progress: Arc::new(ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::hidden())),
+            total_files: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn with_pause_signal(mut self, pause: watch::Receiver<bool>) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    pub fn with_stats(mut self, stats: Arc<DownloadStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn stats(&self) -> &DownloadStats {
+        &self.stats
+    }
+
+    pub fn with_transform(mut self, transform: Option<Arc<dyn StreamTransform>>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn transform(&self) -> Option<&Arc<dyn StreamTransform>> {
+        self.transform.as_ref()
+    }
+
+    pub fn with_sink(mut self, sink: Option<Arc<dyn SinkProvider>>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    // 进度回调按 progress_refresh_ms 节流
+    pub fn with_progress_callback(mut self, callback: Option<Arc<dyn ProgressCallback>>) -> Self {
+        let interval = refresh_interval(&self.config);
+        self.reporter = callback.map(|callback| Arc::new(ProgressReporter::new(callback, interval)));
+        self
+    }
+
+    // 文件开始传输时登记回调进度，downloaded 为续传时已有的字节数
+    pub fn start_callback(&self, filename: &str, downloaded: u64, total: u64) -> Result<(), DownloadError> {
+        match &self.reporter {
+            Some(reporter) => reporter.start(filename, downloaded, total),
+            None => Ok(()),
+        }
+    }
+
+    // 写入数据后更新回调进度；回调出错时返回 Aborted，下载循环据此退出
+    pub fn advance_callback(&self, filename: &str, bytes: u64) -> Result<(), DownloadError> {
+        match &self.reporter {
+            Some(reporter) => reporter.advance(filename, bytes),
+            None => Ok(()),
+        }
+    }
+
+    // 文件下载并校验完成后以 downloaded 等于 total 调用一次回调
+    pub fn finish_callback(&self, filename: &str) -> Result<(), DownloadError> {
+        match &self.reporter {
+            Some(reporter) => reporter.finish(filename),
+            None => Ok(()),
+        }
+    }
+
+    pub fn has_custom_sink(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    // 打开文件的写入目标；本地文件 truncate 为 false 时保留已下载的部分
+    pub async fn open_sink(&self, file: &crate::types::FileInfo, path: &std::path::Path, truncate: bool) -> Result<Arc<dyn DownloadSink>, String> {
+        match &self.sink {
+            Some(provider) => provider.open(file).await,
+            None => Ok(Arc::new(LocalFileSink::open(path, truncate).await?)),
+        }
+    }
+
+    // 设置了 max_connections_per_host 时按请求的主机排队，返回的许可需要保持到请求结束
+    pub async fn host_permit(&self, url: &str) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        match &self.host_limiter {
+            Some(limiter) => limiter.acquire(url).await,
+            None => None,
         }
     }
 
+    // 顺序写入用的缓冲区；设置了 max_buffer_memory 时从共享预算中申请，活跃连接多时缓冲区相应缩小
+    pub async fn buffered_writer(&self, sink: Arc<dyn DownloadSink>, offset: u64) -> BufferedWriter {
+        match &self.buffer_budget {
+            Some(budget) => {
+                let reservation = budget.reserve(self.config.buffer_size).await;
+                BufferedWriter::new(sink, offset, reservation.size).with_reservation(reservation)
+            }
+            None => BufferedWriter::new(sink, offset, self.config.buffer_size),
+        }
+    }
+
+    // 按 max_download_speed 限速，所有写入循环共享同一个令牌桶
+    pub async fn throttle(&self, bytes: u64) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(bytes).await;
+        }
+    }
+
+    pub async fn wait_if_paused(&self) {
+        let mut pause = self.pause.clone();
+        while *pause.borrow_and_update() {
+            if pause.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    #[allow(dead_code)]
     pub async fn get_progress(&self, _filename: &str) -> Arc<ProgressBar> {
         // 如果是文件夹下载，返回文件夹进度条
         if self.is_folder {
@@ -86,8 +298,8 @@ impl DownloadManager {
         // 对于单文件下载，返回对应的进度条
         let file_progress = self.file_progress.lock().await;
         file_progress.get(_filename)
-            .map(|pb| pb.clone())
-            .expect(&format!("Progress bar not found for file: {}", _filename))
+            .cloned()
+            .unwrap_or_else(|| panic!("Progress bar not found for file: {}", _filename))
     }
 
     pub async fn create_file_progress(&self, _filename: String, size: u64) -> Arc<ProgressBar> {
@@ -103,8 +315,38 @@ impl DownloadManager {
         
         // 如果进度条已经存在，先移除它
         if let Some(old_pb) = file_progress.remove(&_filename) {
+            let length = self.total_progress.length().unwrap_or(0);
+            self.total_progress.set_length(length.saturating_sub(old_pb.length().unwrap_or(0)));
             old_pb.finish_and_clear();
+        } else {
+            self.add_to_total().await;
         }
+        self.total_progress.inc_length(size);
+
+        // 同时显示的单文件进度条不超过并发数，其余文件只计入汇总进度条
+        let mut hidden_files = self.hidden_files.lock().await;
+        let visible = file_progress.len() - hidden_files.len();
+        if visible >= self.config.concurrent_downloads.max(1) {
+            let pb = Arc::new(ProgressBar::hidden());
+            pb.set_length(size);
+            hidden_files.insert(_filename.clone());
+            file_progress.insert(_filename.clone(), pb.clone());
+
+            let mut overflow = self.overflow_progress.lock().await;
+            let overflow_pb = overflow.get_or_insert_with(|| {
+                let pb = Arc::new(self.multi_progress.add(ProgressBar::new(0)));
+                pb.set_style(ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
+                    .unwrap()
+                    .progress_chars("#>-"));
+                pb.enable_steady_tick(refresh_interval(&self.config));
+                pb
+            });
+            overflow_pb.inc_length(size);
+            overflow_pb.set_message(format!("Downloading {} more files", hidden_files.len()));
+            return pb;
+        }
+        drop(hidden_files);
 
         let pb = Arc::new(self.multi_progress.add(ProgressBar::new(size)));
         pb.set_style(ProgressStyle::default_bar()
@@ -112,7 +354,7 @@ impl DownloadManager {
             .unwrap()
             .progress_chars("#>-"));
         pb.set_message(format!("Downloading {}", _filename));
-        pb.enable_steady_tick(Duration::from_millis(100));
+        pb.enable_steady_tick(refresh_interval(&self.config));
 
         let task = DownloadTask {
             filename: _filename.clone(),
@@ -127,12 +369,32 @@ impl DownloadManager {
         pb
     }
 
+    // 同时下载多个文件时在最上方显示汇总进度条，显示合计字节数和总吞吐
+    async fn add_to_total(&self) {
+        let mut total_files = self.total_files.lock().await;
+        *total_files += 1;
+        if *total_files == 2 {
+            let pb = self.multi_progress.insert(0, (*self.total_progress).clone());
+            pb.set_style(ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
+                .unwrap()
+                .progress_chars("#>-"));
+            pb.enable_steady_tick(refresh_interval(&self.config));
+        }
+        if *total_files >= 2 {
+            self.total_progress.set_message(format!("Total ({} files)", total_files));
+        }
+    }
+
     pub async fn update_progress(&self, filename: &str, bytes: u64) {
         if self.is_folder {
             // 如果是文件夹下载，只更新文件夹总进度条
             let folder_progress = self.folder_progress.lock().await;
             if let Some(pb) = folder_progress.as_ref() {
                 pb.inc(bytes);
+                if let Some(events) = &self.events {
+                    events.emit(None, pb, false);
+                }
             }
             return;
         }
@@ -140,7 +402,16 @@ impl DownloadManager {
         let file_progress = self.file_progress.lock().await;
         if let Some(pb) = file_progress.get(filename) {
             pb.inc(bytes);
-            pb.set_message(format!("Downloading {}", filename));
+            self.total_progress.inc(bytes);
+            if let Some(events) = &self.events {
+                events.emit(Some(filename), pb, false);
+            }
+            // 文件名在创建进度条时已设置，这里只更新进度
+            if self.hidden_files.lock().await.contains(filename) {
+                if let Some(overflow_pb) = self.overflow_progress.lock().await.as_ref() {
+                    overflow_pb.inc(bytes);
+                }
+            }
         }
     }
 
@@ -152,8 +423,28 @@ impl DownloadManager {
 
         let mut file_progress = self.file_progress.lock().await;
         let mut active_downloads = self.active_downloads.lock().await;
-        
-        if let Some(pb) = file_progress.remove(filename) {
+
+        if let (Some(events), Some(pb)) = (&self.events, file_progress.get(filename)) {
+            events.emit(Some(filename), pb, true);
+        }
+
+        let mut hidden_files = self.hidden_files.lock().await;
+        let overflow = self.overflow_progress.lock().await;
+        if hidden_files.remove(filename) {
+            file_progress.remove(filename);
+            if let Some(overflow_pb) = overflow.as_ref() {
+                if hidden_files.is_empty() {
+                    overflow_pb.finish_with_message("✓ Downloaded remaining files");
+                } else {
+                    overflow_pb.set_message(format!("Downloading {} more files", hidden_files.len()));
+                }
+            }
+        } else if overflow.is_some() {
+            // 文件很多时已完成的进度条直接清除，避免终端被刷屏
+            if let Some(pb) = file_progress.remove(filename) {
+                pb.finish_and_clear();
+            }
+        } else if let Some(pb) = file_progress.remove(filename) {
             pb.finish_with_message(format!("✓ Downloaded {}", filename));
             pb.set_style(ProgressStyle::default_bar()
                 .template("[{elapsed_precise}] [{bar:40.green/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
@@ -163,6 +454,16 @@ impl DownloadManager {
         
         active_downloads.remove(filename);
 
+        // 最后一个文件完成时汇总进度条一起结束
+        let total_files = *self.total_files.lock().await;
+        if file_progress.is_empty() && total_files >= 2 {
+            self.total_progress.finish_with_message(format!("✓ Downloaded {} files", total_files));
+            self.total_progress.set_style(ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] [{bar:40.green/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
+                .unwrap()
+                .progress_chars("#>-"));
+        }
+
         let mut queue = self.download_queue.lock().await;
         if let Some(next_task) = queue.pop_front() {
             active_downloads.insert(next_task.filename.clone(), next_task.clone());
@@ -177,6 +478,9 @@ impl DownloadManager {
 
         let folder_progress = self.folder_progress.lock().await;
         if let Some(pb) = folder_progress.as_ref() {
+            if let Some(events) = &self.events {
+                events.emit(None, pb, true);
+            }
             pb.finish_with_message("✓ Folder download completed");
             pb.set_style(ProgressStyle::default_bar()
                 .template("[{elapsed_precise}] [{bar:40.green/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
@@ -198,6 +502,11 @@ impl DownloadManager {
             for (_, pb) in file_progress.drain() {
                 pb.finish_and_clear();
             }
+            self.hidden_files.lock().await.clear();
+            if let Some(pb) = self.overflow_progress.lock().await.take() {
+                pb.finish_and_clear();
+            }
+            self.total_progress.finish_and_clear();
         }
 
         // 清理下载队列和活动下载
@@ -223,6 +532,9 @@ impl DownloadManager {
         if let Some(pb) = file_progress.get(_filename) {
             pb.abandon_with_message(format!("⚠ Interrupted: {}", _filename));
         }
+        if *self.total_files.lock().await >= 2 {
+            self.total_progress.abandon_with_message("⚠ Download interrupted");
+        }
     }
 
     pub async fn handle_folder_interrupt(&self) {
@@ -245,4 +557,108 @@ impl DownloadManager {
     pub fn get_config(&self) -> Arc<Config> {
         self.config.clone()
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn visible_bars(manager: &DownloadManager) -> usize {
+        manager.file_progress.lock().await.len() - manager.hidden_files.lock().await.len()
+    }
+
+    #[tokio::test]
+    async fn many_files_share_one_overflow_bar() {
+        let config = Config {
+            concurrent_downloads: 4,
+            ..Config::default()
+        };
+        let manager = DownloadManager::new(0, config);
+        // 绘制到内存中的终端，按实际画出的行数统计进度条
+        let term = indicatif::InMemoryTerm::new(100, 200);
+        manager.multi_progress.set_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+        let drawn_bars = || term.contents().lines().filter(|line| !line.trim().is_empty()).count();
+
+        // 始终有 4 个文件在下载，每开始一个新文件就完成最早的一个
+        for i in 0..1000 {
+            manager.create_file_progress(format!("file-{}", i), 10).await;
+            assert!(visible_bars(&manager).await <= 4, "file {}", i);
+            if i >= 4 {
+                manager.finish_file(&format!("file-{}", i - 4)).await;
+            }
+            // 上限只针对单文件进度条；溢出进度条和（两个文件起的）汇总进度条另外显示，不占用名额
+            let overflow = usize::from(manager.overflow_progress.lock().await.is_some());
+            let total = usize::from(i >= 1);
+            assert_eq!(drawn_bars(), visible_bars(&manager).await + overflow + total, "file {}:\n{}", i, term.contents());
+            assert!(drawn_bars() <= 4 + 2, "file {}", i);
+        }
+        assert!(manager.overflow_progress.lock().await.is_some());
+        assert_eq!(manager.file_progress.lock().await.len(), 4);
+
+        for i in 996..1000 {
+            manager.finish_file(&format!("file-{}", i)).await;
+        }
+        assert!(manager.file_progress.lock().await.is_empty());
+        assert!(manager.hidden_files.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pause_halts_writes_and_resume_continues() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+        use std::time::{Duration, Instant};
+
+        let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 253) as u8).collect();
+        let repo = MockRepo::new("org/paused", &[("model.bin", &content)]);
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).paced(4096, Duration::from_millis(20))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/paused/model.bin");
+        let part = progress::part_path(&target);
+        let written = || std::fs::metadata(&part).map_or(0, |m| m.len());
+
+        let mut config = testing::config(&server.url());
+        config.buffer_size = 4096;
+        let handle = crate::ShutdownHandle::new();
+        let download = crate::cli::download_file(
+            config, "org/paused".to_string(), Some(dir.path().to_string_lossy().to_string()),
+            None, None, None, None, None, None, handle.clone(),
+        );
+        let control = async {
+            let started = Instant::now();
+            while written() == 0 {
+                assert!(started.elapsed() < Duration::from_secs(10), "download never started");
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            handle.pause();
+            // 暂停前已经取到的数据写完后，文件不再增长
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let paused_at = written();
+            tokio::time::sleep(Duration::from_millis(400)).await;
+            let still = written();
+            handle.resume();
+            (paused_at, still)
+        };
+        let (result, (paused_at, still)) = tokio::join!(download, control);
+
+        result.unwrap();
+        assert_eq!(paused_at, still);
+        assert!(paused_at < content.len() as u64);
+        assert_eq!(std::fs::read(&target).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn file_message_is_set_once_not_per_chunk() {
+        let manager = DownloadManager::new(0, Config::default());
+        let pb = manager.create_file_progress("model.bin".to_string(), 1000).await;
+        assert_eq!(pb.message(), "Downloading model.bin");
+
+        // 之后的进度更新不再重新格式化消息，手动改过的消息保持不变
+        pb.set_message("unchanged");
+        for _ in 0..100 {
+            manager.update_progress("model.bin", 10).await;
+        }
+        assert_eq!(pb.position(), 1000);
+        assert_eq!(pb.message(), "unchanged");
+    }
+}
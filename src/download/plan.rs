@@ -0,0 +1,380 @@
+use crate::types::FileInfo;
+use super::checksum::{expected_checksum, hash_file};
+use super::layout::{local_path, PathMapping};
+use super::lfs;
+use super::progress::{has_progress, local_progress, part_size};
+use super::repo_cache::RepoCache;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    Download,
+    Skip,
+    Verify,
+}
+
+// 判断本地文件“已下载”的标准
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkipCriterion {
+    // 本地大小不小于远端大小
+    #[default]
+    #[serde(rename = "size")]
+    Size,
+    // 大小一致，且本地文件不早于服务器的 Last-Modified
+    #[serde(rename = "size+mtime")]
+    SizeMtime,
+    // 大小一致，且摘要与元数据一致（没有摘要时退回按大小判断）
+    #[serde(rename = "sha256")]
+    Sha256,
+}
+
+impl FromStr for SkipCriterion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "size" => Ok(Self::Size),
+            "size+mtime" => Ok(Self::SizeMtime),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(format!("Unknown skip criterion '{}', expected size, size+mtime or sha256", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedFile {
+    #[serde(flatten)]
+    pub file: FileInfo,
+    pub local_size: u64,
+    pub action: PlanAction,
+}
+
+// 下载计划：预览（--dry-run / --list）和实际下载共用同一份计算结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadPlan {
+    pub repo_id: String,
+    pub is_dataset: bool,
+    pub destination: PathBuf,
+    pub files: Vec<PlannedFile>,
+    pub total_bytes: u64,
+    pub download_bytes: u64,
+    // 解析文件列表时仓库所在的 commit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    // 下载成功后写入下载目录的文件列表缓存
+    #[serde(skip)]
+    pub repo_cache: Option<RepoCache>,
+}
+
+impl DownloadPlan {
+    pub fn file_infos(&self) -> Vec<FileInfo> {
+        self.files.iter()
+            .map(|f| f.file.clone())
+            .collect()
+    }
+
+    pub fn count(&self, action: PlanAction) -> usize {
+        self.files.iter().filter(|f| f.action == action).count()
+    }
+}
+
+// 与下载路径一致的跳过判断：本地大小不小于远端大小即视为已完成
+pub fn is_complete(local_size: u64, size: Option<u64>) -> bool {
+    matches!(size, Some(size) if local_size >= size)
+}
+
+// 在大小判断之外按 criterion 进一步确认本地文件可以跳过
+async fn meets_criterion(path: &Path, file: &FileInfo, metadata: &std::fs::Metadata, criterion: SkipCriterion) -> bool {
+    match criterion {
+        SkipCriterion::Size => true,
+        SkipCriterion::SizeMtime => {
+            let remote = file.last_modified.as_deref()
+                .and_then(|value| httpdate::parse_http_date(value).ok());
+            match (remote, metadata.modified()) {
+                (Some(remote), Ok(local)) => Some(metadata.len()) == file.size && local >= remote,
+                _ => Some(metadata.len()) == file.size,
+            }
+        }
+        SkipCriterion::Sha256 => match expected_checksum(file, None) {
+            Some((algo, expected)) => matches!(hash_file(path, algo).await, Ok(digest) if digest == expected),
+            None => true,
+        },
+    }
+}
+
+// 待下载文件还需要传输的字节数；未通过 criterion 的完整文件需要整体重新下载
+pub fn remaining_bytes(local_size: u64, size: u64) -> u64 {
+    if local_size >= size { size } else { size - local_size }
+}
+
+// 文件大小已达到但未通过 criterion，需要从头重新下载
+pub fn needs_redownload(planned: &PlannedFile) -> bool {
+    planned.action == PlanAction::Download && is_complete(planned.local_size, planned.file.size)
+}
+
+// 计算下载计划，不修改磁盘；verify 为 true 时已完成的文件标记为待校验而非跳过
+pub async fn plan(
+    repo_id: &str,
+    is_dataset: bool,
+    destination: &Path,
+    files: Vec<FileInfo>,
+    path_map: &[PathMapping],
+    criterion: SkipCriterion,
+    verify: bool,
+) -> DownloadPlan {
+    let mut planned = Vec::with_capacity(files.len());
+    let mut total_bytes = 0;
+    let mut download_bytes = 0;
+
+    for file in files {
+        // 目标路径是目录或符号链接时视为不存在，由下载前的冲突检查处理
+        let path = local_path(destination, &file.rfilename, path_map);
+        let (local_size, complete) = match tokio::fs::symlink_metadata(&path).await {
+            // 分块下载中断后留下进度文件，只有已完成的块算作已下载
+            _ if has_progress(&path) => (local_progress(&path).unwrap_or(0), false),
+            Ok(metadata) if metadata.is_file() => {
+                // 上次留下的 LFS 指针不算已下载
+                let complete = is_complete(metadata.len(), file.size)
+                    && !(lfs::is_lfs(&file) && lfs::is_pointer(&path))
+                    && meets_criterion(&path, &file, &metadata, criterion).await;
+                // 未完成的部分在临时文件中；大小已满足但未通过 criterion 的文件保留本地大小，
+                // 下载前据此删除后整体重新下载
+                let local_size = if is_complete(metadata.len(), file.size) { metadata.len() } else { part_size(&path) };
+                (local_size, complete)
+            }
+            Ok(_) => (0, false),
+            Err(_) => (part_size(&path), false),
+        };
+
+        let action = if !complete {
+            PlanAction::Download
+        } else if verify {
+            PlanAction::Verify
+        } else {
+            PlanAction::Skip
+        };
+
+        if let Some(size) = file.size {
+            total_bytes += size;
+            if action == PlanAction::Download {
+                download_bytes += remaining_bytes(local_size, size);
+            }
+        }
+
+        planned.push(PlannedFile {
+            file,
+            local_size,
+            action,
+        });
+    }
+
+    DownloadPlan {
+        repo_id: repo_id.to_string(),
+        is_dataset,
+        destination: destination.to_path_buf(),
+        files: planned,
+        total_bytes,
+        download_bytes,
+        commit: None,
+        repo_cache: None,
+    }
+}
+
+// 旧副本中的文件与远端大小一致，且有摘要时摘要也一致
+async fn matches_snapshot(path: &Path, file: &FileInfo) -> bool {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() && Some(metadata.len()) == file.size => {
+            match expected_checksum(file, None) {
+                Some((algo, expected)) => matches!(hash_file(path, algo).await, Ok(digest) if digest == expected),
+                None => true,
+            }
+        }
+        _ => false,
+    }
+}
+
+// 从计划中去掉目标目录里缺失、但在 snapshot 目录中已有相同内容的文件，返回去掉的文件数
+pub async fn exclude_snapshot(download_plan: &mut DownloadPlan, snapshot: &Path, path_map: &[PathMapping]) -> usize {
+    let mut files = Vec::with_capacity(download_plan.files.len());
+    let mut excluded = 0;
+
+    for planned in std::mem::take(&mut download_plan.files) {
+        let unchanged = planned.action == PlanAction::Download
+            && matches_snapshot(&local_path(snapshot, &planned.file.rfilename, path_map), &planned.file).await;
+        if !unchanged {
+            files.push(planned);
+            continue;
+        }
+
+        excluded += 1;
+        if let Some(size) = planned.file.size {
+            download_plan.total_bytes -= size;
+            download_plan.download_bytes -= remaining_bytes(planned.local_size, size);
+        }
+    }
+
+    download_plan.files = files;
+    excluded
+}
+
+// 设置了字节流变换时，已记录为变换完成的文件不再下载；其余文件总是从头下载
+pub async fn exclude_transformed(download_plan: &mut DownloadPlan, path_map: &[PathMapping]) -> usize {
+    let record = super::transform::load_record(&download_plan.destination);
+    let mut files = Vec::with_capacity(download_plan.files.len());
+    let mut excluded = 0;
+
+    for planned in std::mem::take(&mut download_plan.files) {
+        let path = local_path(&download_plan.destination, &planned.file.rfilename, path_map);
+        if !super::transform::is_transformed_complete(&record, &planned.file, &path).await {
+            files.push(planned);
+            continue;
+        }
+
+        excluded += 1;
+        if let Some(size) = planned.file.size {
+            download_plan.total_bytes -= size;
+            // 变换后变小的文件可能被判断为已存在，没有计入待下载字节
+            if planned.action == PlanAction::Download {
+                download_plan.download_bytes -= remaining_bytes(planned.local_size, size);
+            }
+        }
+    }
+
+    download_plan.files = files;
+    excluded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::progress::part_path;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn complete_when_local_size_reaches_remote_size() {
+        assert!(is_complete(10, Some(10)));
+        assert!(is_complete(11, Some(10)));
+        assert!(!is_complete(9, Some(10)));
+        // 大小未知时无法判断，总是重新下载
+        assert!(!is_complete(10, None));
+    }
+
+    #[tokio::test]
+    async fn plan_matches_what_the_download_fetches() {
+        let repo = MockRepo::new("org/mixed", &[
+            ("config.json", b"{\"hidden_size\": 8}"),
+            ("model.bin", b"0123456789abcdefghij"),
+            ("README.md", b"# mixed"),
+            ("data/train.txt", b"line 1\nline 2\n"),
+        ]);
+        let server = repo.clone().serve();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/mixed");
+        std::fs::create_dir_all(&target).unwrap();
+        // 已下载完成、下载了一半、未下载各有一些
+        std::fs::write(target.join("config.json"), repo.content("config.json")).unwrap();
+        std::fs::write(part_path(&target.join("model.bin")), &repo.content("model.bin")[..8]).unwrap();
+
+        let config = testing::config(&server.url());
+        let local_dir = Some(dir.path().to_string_lossy().to_string());
+        let preview = crate::cli::list_files(config.clone(), "org/mixed".to_string(), local_dir, None, None, None)
+            .await
+            .unwrap();
+
+        let action = |name: &str| preview.files.iter().find(|f| f.file.rfilename == name).unwrap().action;
+        assert_eq!(action("config.json"), PlanAction::Skip);
+        assert_eq!(action("model.bin"), PlanAction::Download);
+        assert_eq!(action("README.md"), PlanAction::Download);
+        assert_eq!(action("data/train.txt"), PlanAction::Download);
+        assert_eq!(preview.destination, target);
+        assert_eq!(preview.total_bytes, repo.files.iter().map(|(_, c)| c.len() as u64).sum::<u64>());
+        assert_eq!(preview.download_bytes, 12 + 7 + 14);
+
+        let (_, summary) = testing::download(config, "org/mixed", dir.path()).await.unwrap();
+
+        // 实际下载的正是计划中标记为 Download 的文件，续传的文件从已有部分之后开始
+        let mut fetched = server.downloads();
+        fetched.sort();
+        let mut planned: Vec<_> = preview.files.iter()
+            .filter(|f| f.action == PlanAction::Download)
+            .map(|f| f.file.rfilename.clone())
+            .collect();
+        planned.sort();
+        assert_eq!(fetched, planned);
+        let resumed = server.requests().into_iter()
+            .find(|r| r.method == "GET" && r.path.ends_with("/model.bin"))
+            .unwrap();
+        assert_eq!(resumed.header("range"), Some("bytes=8-"));
+        assert_eq!(summary.files_downloaded, 3);
+        assert_eq!(summary.files_skipped, 1);
+        assert_eq!(summary.bytes_downloaded, preview.download_bytes);
+        for (path, content) in &repo.files {
+            assert_eq!(&std::fs::read(target.join(path)).unwrap(), content, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn sha256_criterion_redownloads_same_size_corrupt_file() {
+        let repo = MockRepo::new("org/corrupt", &[("model.bin", b"good weights")]);
+        let server = repo.serve();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/corrupt/model.bin");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::write(&target, b"bad  weights").unwrap();
+
+        // 默认只比较大小，大小相同的损坏文件被跳过
+        testing::download(testing::config(&server.url()), "org/corrupt", dir.path()).await.unwrap();
+        assert!(server.downloads().is_empty());
+        assert_eq!(std::fs::read(&target).unwrap(), b"bad  weights");
+
+        let mut config = testing::config(&server.url());
+        config.skip_criterion = SkipCriterion::Sha256;
+        testing::download(config, "org/corrupt", dir.path()).await.unwrap();
+        assert_eq!(server.downloads(), ["model.bin"]);
+        assert_eq!(std::fs::read(&target).unwrap(), b"good weights");
+        // 整体重新下载，不从已有内容之后续传
+        let request = server.requests().into_iter().find(|r| r.method == "GET").unwrap();
+        assert_eq!(request.header("range"), None);
+    }
+
+    #[test]
+    fn remaining_bytes_counts_only_what_is_missing() {
+        assert_eq!(remaining_bytes(0, 10), 10);
+        assert_eq!(remaining_bytes(4, 10), 6);
+        // 完整但需要重新下载的文件整体计入
+        assert_eq!(remaining_bytes(10, 10), 10);
+        assert_eq!(remaining_bytes(12, 10), 10);
+    }
+
+    #[tokio::test]
+    async fn diff_against_fetches_only_changed_and_new_files() {
+        let repo = MockRepo::new("org/update", &[
+            ("same.bin", b"unchanged"),
+            ("changed.bin", b"version 2"),
+            ("new.bin", b"added"),
+        ]);
+        let server = repo.serve();
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("old");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("same.bin"), b"unchanged").unwrap();
+        // 大小相同但内容不同，按 sha256 判断为已变化
+        std::fs::write(snapshot.join("changed.bin"), b"version 1").unwrap();
+
+        let mut config = testing::config(&server.url());
+        config.diff_against = Some(snapshot.to_string_lossy().to_string());
+        let (path, _) = testing::download(config, "org/update", &dir.path().join("new")).await.unwrap();
+
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["changed.bin", "new.bin"]);
+        let path = Path::new(&path);
+        assert_eq!(std::fs::read(path.join("changed.bin")).unwrap(), b"version 2");
+        assert_eq!(std::fs::read(path.join("new.bin")).unwrap(), b"added");
+        assert!(!path.join("same.bin").exists());
+    }
+}
+
@@ -0,0 +1,122 @@
+use crate::types::FileInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+// 同一份权重在仓库中常以多种格式存在，优先下载的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreferFormat {
+    Safetensors,
+}
+
+impl FromStr for PreferFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "safetensors" => Ok(Self::Safetensors),
+            _ => Err(format!("Unknown format '{}', expected safetensors", s)),
+        }
+    }
+}
+
+// 其他框架的权重文件名前缀与扩展名（含分片和分片索引）
+const REDUNDANT_WEIGHTS: &[(&str, &str)] = &[
+    ("pytorch_model", ".bin"),
+    ("pytorch_model", ".bin.index.json"),
+    ("tf_model", ".h5"),
+    ("tf_model", ".h5.index.json"),
+    ("flax_model", ".msgpack"),
+    ("flax_model", ".msgpack.index.json"),
+];
+
+fn split_dir(rfilename: &str) -> (&str, &str) {
+    rfilename.rsplit_once('/').unwrap_or(("", rfilename))
+}
+
+// 同一目录下存在 safetensors 权重时，去掉冗余的其他格式权重
+pub fn apply(files: &mut Vec<FileInfo>, prefer: PreferFormat) {
+    match prefer {
+        PreferFormat::Safetensors => {
+            let dirs_with_safetensors: HashSet<String> = files.iter()
+                .filter(|f| f.rfilename.ends_with(".safetensors"))
+                .map(|f| split_dir(&f.rfilename).0.to_string())
+                .collect();
+
+            files.retain(|f| {
+                let (dir, name) = split_dir(&f.rfilename);
+                let redundant = REDUNDANT_WEIGHTS.iter()
+                    .any(|(prefix, ext)| name.starts_with(prefix) && name.ends_with(ext));
+                !(redundant && dirs_with_safetensors.contains(dir))
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    fn file(name: &str) -> FileInfo {
+        FileInfo { rfilename: name.to_string(), size: Some(1), sha256: None, blob_id: None, last_modified: None }
+    }
+
+    fn names(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|f| f.rfilename.as_str()).collect()
+    }
+
+    #[test]
+    fn drops_other_formats_next_to_safetensors() {
+        let mut files: Vec<_> = [
+            "config.json",
+            "model-00001-of-00002.safetensors",
+            "model-00002-of-00002.safetensors",
+            "model.safetensors.index.json",
+            "pytorch_model-00001-of-00002.bin",
+            "pytorch_model-00002-of-00002.bin",
+            "pytorch_model.bin.index.json",
+            "tf_model.h5",
+            "flax_model.msgpack",
+            // 没有 safetensors 的目录保留原有格式
+            "legacy/pytorch_model.bin",
+            "training_args.bin",
+        ].into_iter().map(file).collect();
+
+        apply(&mut files, PreferFormat::Safetensors);
+        assert_eq!(names(&files), [
+            "config.json",
+            "model-00001-of-00002.safetensors",
+            "model-00002-of-00002.safetensors",
+            "model.safetensors.index.json",
+            "legacy/pytorch_model.bin",
+            "training_args.bin",
+        ]);
+    }
+
+    #[test]
+    fn keeps_bins_without_safetensors() {
+        let mut files = vec![file("config.json"), file("pytorch_model.bin")];
+        apply(&mut files, PreferFormat::Safetensors);
+        assert_eq!(names(&files), ["config.json", "pytorch_model.bin"]);
+    }
+
+    #[tokio::test]
+    async fn dual_format_repo_fetches_only_safetensors() {
+        let server = MockRepo::new("org/dual", &[
+            ("config.json", b"{}"),
+            ("model.safetensors", b"safetensors weights"),
+            ("pytorch_model.bin", b"pickled weights"),
+        ]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.prefer = Some(PreferFormat::Safetensors);
+        testing::download(config, "org/dual", dir.path()).await.unwrap();
+
+        let mut fetched = server.downloads();
+        fetched.sort();
+        assert_eq!(fetched, ["config.json", "model.safetensors"]);
+        assert!(!dir.path().join("org/dual/pytorch_model.bin").exists());
+    }
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+// 分块下载的进度文件后缀，与目标文件放在同一目录
+pub const PROGRESS_SUFFIX: &str = ".hfd-progress";
+
+// 下载中的数据先写入 <目标文件>.hfd-part，大小（和摘要）校验通过后再改名为目标文件，
+// 目标路径上只会出现完整的文件
+pub const PART_SUFFIX: &str = ".hfd-part";
+
+// 下载目录中的进行中标记：存在说明上次下载没有完成（或正在进行），外部工具应等它消失后再使用目录
+pub const DOWNLOADING_MARKER: &str = ".downloading";
+
+// 写入进行中标记，返回标记之前是否已经存在（即上次下载未完成）
+pub fn mark_downloading(dir: &Path, repo_id: &str) -> Result<bool, String> {
+    let path = dir.join(DOWNLOADING_MARKER);
+    let existed = path.exists();
+    std::fs::write(&path, format!("{}\n", repo_id))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(existed)
+}
+
+pub fn clear_downloading(dir: &Path) -> Result<(), String> {
+    let path = dir.join(DOWNLOADING_MARKER);
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", path.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+// 中断时删除未完成的临时文件和进度文件（discard_partial_on_interrupt），下次从头下载
+pub fn discard_partial(path: &Path) {
+    let _ = std::fs::remove_file(part_path(path));
+    ChunkProgress::remove(path);
+}
+
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(PART_SUFFIX);
+    PathBuf::from(name)
+}
+
+// 已下载的临时文件长度，没有时为 0
+pub fn part_size(path: &Path) -> u64 {
+    std::fs::metadata(part_path(path))
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+// 校验通过后把临时文件改名为目标文件，替换已有的旧文件
+pub async fn commit_part(path: &Path) -> Result<(), String> {
+    let part = part_path(path);
+    tokio::fs::rename(&part, path)
+        .await
+        .map_err(|e| format!("Failed to move {} to {}: {}", part.display(), path.display(), e))
+}
+
+// 分块下载中已写完的块。各块并发写入，文件长度不能说明哪些块已经完整，
+// 进程中途退出后按这里的记录只重新下载未完成的块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkProgress {
+    pub size: u64,
+    pub chunk_size: u64,
+    pub completed: BTreeSet<u64>,
+}
+
+pub fn progress_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(PROGRESS_SUFFIX);
+    PathBuf::from(name)
+}
+
+pub fn has_progress(path: &Path) -> bool {
+    progress_path(path).exists()
+}
+
+// 有进度文件时返回已完成块的字节数（进度文件损坏时为 0），没有时返回 None
+pub fn local_progress(path: &Path) -> Option<u64> {
+    has_progress(path).then(|| {
+        ChunkProgress::load(path)
+            .map(|progress| progress.completed_bytes())
+            .unwrap_or(0)
+    })
+}
+
+impl ChunkProgress {
+    pub fn new(size: u64, chunk_size: u64) -> Self {
+        Self {
+            size,
+            chunk_size,
+            completed: BTreeSet::new(),
+        }
+    }
+
+    // 读取目标文件对应的进度文件，不存在或无法解析时返回 None
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(progress_path(path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    // 先写临时文件再改名，中途退出不会留下半个进度文件
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let target = progress_path(path);
+        let mut temp = target.clone().into_os_string();
+        temp.push(".tmp");
+        let content = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize chunk progress: {}", e))?;
+        std::fs::write(&temp, content)
+            .and_then(|_| std::fs::rename(&temp, &target))
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+    }
+
+    pub fn remove(path: &Path) {
+        let _ = std::fs::remove_file(progress_path(path));
+    }
+
+    // 换算成新的块大小：新块覆盖的字节都在已完成的旧块中时记为完成，其余重新下载
+    pub fn rechunk(&self, chunk_size: u64) -> Self {
+        let old_chunk_size = self.chunk_size.max(1);
+        let completed = (0..self.size.div_ceil(chunk_size.max(1)))
+            .filter(|index| {
+                let start = index * chunk_size;
+                let end = (start + chunk_size).min(self.size);
+                (start / old_chunk_size..end.div_ceil(old_chunk_size))
+                    .all(|old| self.completed.contains(&old))
+            })
+            .collect();
+        Self {
+            size: self.size,
+            chunk_size,
+            completed,
+        }
+    }
+
+    pub fn completed_bytes(&self) -> u64 {
+        self.completed.iter()
+            .map(|index| {
+                let start = index * self.chunk_size;
+                self.size.saturating_sub(start).min(self.chunk_size)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use std::time::{Duration, Instant};
+
+    fn content() -> Vec<u8> {
+        (0..64 * 1024).map(|i| (i % 253) as u8).collect()
+    }
+
+    // 慢速返回 model.bin 的仓库
+    fn slow_repo(repo_id: &str) -> MockServer {
+        let repo = MockRepo::new(repo_id, &[("model.bin", &content())]);
+        MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).paced(4096, Duration::from_millis(20))
+        })
+    }
+
+    async fn download(config: Config, repo_id: &str, dir: &Path, handle: crate::ShutdownHandle) -> pyo3::PyResult<(String, crate::download::stats::DownloadSummary)> {
+        crate::cli::download_file(
+            config, repo_id.to_string(), Some(dir.to_string_lossy().to_string()),
+            None, None, None, None, None, None, handle,
+        ).await
+    }
+
+    // 临时文件写入数据后中断下载，返回中断前 check 的结果
+    async fn interrupt_after_first_write<T>(config: Config, repo_id: &str, dir: &Path, check: impl FnOnce() -> T) -> T {
+        let part = part_path(&dir.join(repo_id).join("model.bin"));
+        let handle = crate::ShutdownHandle::new();
+        let interrupt = async {
+            let started = Instant::now();
+            while std::fs::metadata(&part).map_or(0, |m| m.len()) == 0 {
+                assert!(started.elapsed() < Duration::from_secs(10), "download never started");
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let checked = check();
+            handle.shutdown();
+            checked
+        };
+        let (result, checked) = tokio::join!(download(config, repo_id, dir, handle.clone()), interrupt);
+        assert!(result.is_err());
+        checked
+    }
+
+    #[tokio::test]
+    async fn downloading_marker_outlives_an_interruption() {
+        let server = slow_repo("org/marked");
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/marked");
+        let marker = target.join(DOWNLOADING_MARKER);
+        let mut config = testing::config(&server.url());
+        config.buffer_size = 4096;
+        config.downloading_marker = true;
+
+        // 下载进行中标记存在，中断后仍然保留
+        let present_during = interrupt_after_first_write(config.clone(), "org/marked", dir.path(), || marker.exists()).await;
+        assert!(present_during);
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "org/marked\n");
+
+        // 续传成功后标记被删除
+        download(config, "org/marked", dir.path(), crate::ShutdownHandle::new()).await.unwrap();
+        assert!(!marker.exists());
+        assert_eq!(std::fs::read(target.join("model.bin")).unwrap(), content());
+    }
+
+    #[tokio::test]
+    async fn interrupt_keeps_the_partial_unless_discarding() {
+        let server = slow_repo("org/partial");
+        let target = |dir: &Path| dir.join("org/partial/model.bin");
+        let mut config = testing::config(&server.url());
+        config.buffer_size = 4096;
+        config.parallel_download_threshold = 4096;
+        config.chunk_size = 16 * 1024;
+
+        // 默认保留临时文件和块进度，续传时只请求未完成的部分
+        let dir = tempfile::tempdir().unwrap();
+        interrupt_after_first_write(config.clone(), "org/partial", dir.path(), || ()).await;
+        assert!(part_path(&target(dir.path())).exists());
+        let completed = ChunkProgress::load(&target(dir.path())).unwrap().completed.len();
+        let before = server.requests().len();
+        download(config.clone(), "org/partial", dir.path(), crate::ShutdownHandle::new()).await.unwrap();
+        let ranges = server.requests()[before..].iter().filter(|r| r.range().is_some()).count();
+        assert_eq!(ranges, 4 - completed);
+        assert_eq!(std::fs::read(target(dir.path())).unwrap(), content());
+
+        // discard_partial_on_interrupt 时中断后什么都不留下
+        let dir = tempfile::tempdir().unwrap();
+        config.discard_partial_on_interrupt = true;
+        interrupt_after_first_write(config, "org/partial", dir.path(), || ()).await;
+        assert!(!part_path(&target(dir.path())).exists());
+        assert!(ChunkProgress::load(&target(dir.path())).is_none());
+        assert!(!target(dir.path()).exists());
+    }
+
+    fn chunk_progress(size: u64, chunk_size: u64, completed: &[u64]) -> ChunkProgress {
+        ChunkProgress { size, chunk_size, completed: completed.iter().copied().collect() }
+    }
+
+    #[test]
+    fn completed_bytes_counts_the_short_last_chunk() {
+        assert_eq!(chunk_progress(100, 16, &[]).completed_bytes(), 0);
+        assert_eq!(chunk_progress(100, 16, &[0, 1]).completed_bytes(), 32);
+        assert_eq!(chunk_progress(100, 16, &[6]).completed_bytes(), 4);
+        assert_eq!(chunk_progress(100, 16, &[0, 1, 2, 3, 4, 5, 6]).completed_bytes(), 100);
+    }
+
+    #[test]
+    fn rechunk_keeps_only_fully_covered_chunks() {
+        // 旧块 0-2 覆盖 0..48，新的 24 字节块 0、1 都在其中
+        let larger = chunk_progress(100, 16, &[0, 1, 2]).rechunk(24);
+        assert_eq!(larger.chunk_size, 24);
+        assert_eq!(larger.completed.iter().copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(larger.completed_bytes(), 48);
+
+        // 中间缺了旧块 1，覆盖它的新块都要重新下载
+        let gap = chunk_progress(100, 16, &[0, 2, 3, 4]).rechunk(24);
+        assert_eq!(gap.completed.iter().copied().collect::<Vec<_>>(), [2]);
+
+        // 换成更小的块时拆开已完成的块，最后一块只有 4 字节
+        let smaller = chunk_progress(100, 16, &[0, 6]).rechunk(8);
+        assert_eq!(smaller.completed.iter().copied().collect::<Vec<_>>(), [0, 1, 12]);
+        assert_eq!(smaller.completed_bytes(), 20);
+    }
+
+    #[tokio::test]
+    async fn resume_after_chunk_size_change_is_not_corrupted() {
+        let content: Vec<u8> = (0..100u8).collect();
+        let server = MockRepo::new("org/rechunked", &[("model.bin", &content)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/rechunked/model.bin");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        // 上次以 16 字节的块下载了前三块，其余位置是无效数据
+        let mut partial = content[..48].to_vec();
+        partial.resize(100, 0xff);
+        std::fs::write(part_path(&target), partial).unwrap();
+        chunk_progress(100, 16, &[0, 1, 2]).save(&target).unwrap();
+
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 16;
+        config.chunk_size = 24;
+        testing::download(config, "org/rechunked", dir.path()).await.unwrap();
+
+        let mut ranges: Vec<_> = server.requests().iter().filter(|r| r.method == "GET").filter_map(|r| r.range()).collect();
+        ranges.sort();
+        assert_eq!(ranges, [(48, Some(71)), (72, Some(95)), (96, Some(99))]);
+        assert_eq!(std::fs::read(&target).unwrap(), content);
+        assert!(!has_progress(&target));
+    }
+}
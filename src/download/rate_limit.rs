@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// 全局令牌桶限速：所有文件和分块共享，按字节计数，最多积攒一秒的突发量
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    // (可用令牌数, 上次补充时间)；令牌可以为负，表示需要等待偿还的欠额
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            state: Mutex::new((bytes_per_sec as f64, Instant::now())),
+        }
+    }
+
+    // 取走 bytes 个令牌，桶里不够时等待到欠额被补上
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut state = self.state.lock().await;
+            let (tokens, last) = &mut *state;
+            let now = Instant::now();
+            let rate = self.bytes_per_sec as f64;
+            *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(rate);
+            *last = now;
+            *tokens -= bytes as f64;
+            if *tokens < 0.0 {
+                Duration::from_secs_f64(-*tokens / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    fn assert_between(elapsed: Duration, min_ms: u64, max_ms: u64) {
+        assert!(
+            elapsed >= Duration::from_millis(min_ms) && elapsed <= Duration::from_millis(max_ms),
+            "{:?} not within {}..{}ms", elapsed, min_ms, max_ms
+        );
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_once_the_burst_is_used() {
+        let limiter = RateLimiter::new(10_000);
+        let started = Instant::now();
+        // 开始时桶是满的，一秒的量不需要等待
+        limiter.acquire(10_000).await;
+        assert_between(started.elapsed(), 0, 50);
+        limiter.acquire(5_000).await;
+        assert_between(started.elapsed(), 450, 700);
+    }
+
+    #[tokio::test]
+    async fn concurrent_acquires_share_one_bucket() {
+        let limiter = RateLimiter::new(10_000);
+        limiter.acquire(10_000).await;
+        let started = Instant::now();
+        tokio::join!(limiter.acquire(5_000), limiter.acquire(5_000));
+        assert_between(started.elapsed(), 900, 1300);
+    }
+
+    #[tokio::test]
+    async fn max_download_speed_caps_total_throughput() {
+        let a = vec![b'a'; 20 * 1024];
+        let b = vec![b'b'; 20 * 1024];
+        let server = MockRepo::new("org/capped", &[("a.bin", &a), ("b.bin", &b)]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.max_download_speed = Some(20 * 1024);
+        config.concurrent_downloads = 2;
+
+        // 两个文件同时下载，共 40KB：第一秒的突发量之后按 20KB/s 传完剩下的一半
+        let started = Instant::now();
+        testing::download(config, "org/capped", dir.path()).await.unwrap();
+        assert_between(started.elapsed(), 800, 2500);
+        assert_eq!(std::fs::read(dir.path().join("org/capped/a.bin")).unwrap(), a);
+        assert_eq!(std::fs::read(dir.path().join("org/capped/b.bin")).unwrap(), b);
+    }
+}
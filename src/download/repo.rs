@@ -3,147 +3,863 @@ use crate::types::{FileInfo, RepoInfo, Auth};
 use crate::config::Config;
 use pyo3::prelude::*;
 use serde_json::Value;
-use futures::future::join_all;
-use tokio::sync::Semaphore;
-use std::sync::Arc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use super::error::AccessDenial;
+use super::repo_cache::RepoCache;
+use super::failures::{backoff_delay, is_retryable_status};
+
+// 去掉首尾空白和多余的 `/`（如 `Org/Repo/`）；Hub 的仓库名区分大小写，大小写保持不变
+pub fn normalize_repo_id(repo_id: &str) -> String {
+    repo_id.trim().trim_matches('/').to_string()
+}
+
+// 服务器返回的仓库名只有大小写不同时提示，下载仍使用用户给出的名字
+fn warn_case_mismatch(repo_id: &str, json: &Value) {
+    if let Some(id) = json["id"].as_str() {
+        if id != repo_id && id.eq_ignore_ascii_case(repo_id) {
+            crate::warnings::warn(format!(
+                "Repository {} resolved to {}; repo ids are case-sensitive, consider using the exact name",
+                repo_id, id
+            ));
+        }
+    }
+}
+
+// 分支名中的 `/`（如 refs/pr/1）需要编码后才能放进 URL 路径
+fn encode_revision(revision: &str) -> String {
+    revision.replace('/', "%2F")
+}
+
+// 文件下载地址，revision 为 None 时使用 main
+pub fn resolve_url(endpoint: &str, repo_id: &str, revision: Option<&str>, rfilename: &str, is_dataset: bool) -> String {
+    let revision = encode_revision(revision.unwrap_or("main"));
+    if is_dataset {
+        format!("{}/datasets/{}/resolve/{}/{}", endpoint, repo_id, revision, rfilename)
+    } else {
+        format!("{}/{}/resolve/{}/{}", endpoint, repo_id, revision, rfilename)
+    }
+}
+
+// 仓库信息接口地址，指定 revision 时查询该 revision 的文件列表
+fn api_url(endpoint: &str, kind: &str, repo_id: &str, revision: Option<&str>) -> String {
+    match revision {
+        Some(revision) => format!("{}/api/{}/{}/revision/{}?blobs=true", endpoint, kind, repo_id, encode_revision(revision)),
+        None => format!("{}/api/{}/{}?blobs=true", endpoint, kind, repo_id),
+    }
+}
+
+// 仓库信息响应：NotModified 表示带 If-None-Match 的请求返回了 304，缓存的文件列表仍然有效
+enum RepoJson {
+    Fetched(Value, Option<String>),
+    NotModified,
+}
+
+// 请求仓库信息，状态码不是成功时返回拒绝原因。
+// 连接失败、retry_statuses 中的状态码（5xx、429）以及响应体被截断（连接中途断开、长度与 Content-Length 不符、
+// JSON 提前结束）时按指数退避重试最多 max_retries 次，401/404 等直接返回；重试用尽后报告最后的错误
+async fn fetch_repo_json(
+    client: &Client,
+    config: &Config,
+    url: &str,
+    auth: &Auth,
+    etag: Option<&str>,
+) -> PyResult<Result<RepoJson, AccessDenial>> {
+    let mut attempts = 0;
+    loop {
+        let mut request = client.get(url);
+        if let Some(token) = &auth.token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+
+        let error = match request.send().await {
+            Ok(response) if response.status() == reqwest::StatusCode::NOT_MODIFIED => return Ok(Ok(RepoJson::NotModified)),
+            Ok(response) if response.status().is_success() => {
+                let expected = response.content_length();
+                let etag = response.headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                match response.bytes().await {
+                    Ok(body) if expected.is_some_and(|len| body.len() as u64 != len) => {
+                        format!("response was truncated: received {} of {} bytes", body.len(), expected.unwrap_or_default())
+                    }
+                    Ok(body) => match serde_json::from_slice::<Value>(&body) {
+                        // Hub 有时以 200 返回 {"error": "..."}，按错误信息归类，无法归类时直接报告服务器的信息
+                        Ok(json) if json["error"].is_string() && json.get("siblings").is_none() => {
+                            let message = json["error"].as_str().unwrap_or_default();
+                            return match AccessDenial::from_message(message) {
+                                Some(denial) => Ok(Err(denial)),
+                                None => Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                                    "Failed to get repo info: {}", message
+                                ))),
+                            };
+                        }
+                        Ok(json) => return Ok(Ok(RepoJson::Fetched(json, etag))),
+                        Err(e) if e.is_eof() => format!("response was truncated: {}", e),
+                        Err(e) => {
+                            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to parse repo info: {}", e)));
+                        }
+                    },
+                    Err(e) => format!("response was truncated: {}", e),
+                }
+            }
+            Ok(response) if is_retryable_status(response.status(), config) => format!("HTTP {}", response.status()),
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.text().await.ok();
+                return Ok(Err(AccessDenial::from_response(status, &headers, body.as_deref())));
+            }
+            Err(e) => e.to_string(),
+        };
+
+        attempts += 1;
+        if attempts >= config.max_retries.max(1) {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "Failed to get repo info after {} attempts: {}", attempts, error
+            )));
+        }
+        tokio::time::sleep(backoff_delay(attempts)).await;
+    }
+}
+
+fn repo_info(config: &Config, repo_id: &str, is_dataset: bool, files: Vec<FileInfo>, commit: Option<String>, etag: Option<String>) -> RepoInfo {
+    let endpoint = |kind| format!("{}/{}/{}", config.endpoint, kind, repo_id);
+    RepoInfo {
+        model_endpoint: (!is_dataset).then(|| endpoint("models")),
+        dataset_endpoint: is_dataset.then(|| endpoint("datasets")),
+        files,
+        commit,
+        etag,
+    }
+}
+
+// 仓库信息没有变化（304）时使用缓存的文件列表；缓存只对同一类型的仓库发送 ETag
+async fn fetch_repo_info(
+    client: &Client,
+    config: &Config,
+    repo_id: &str,
+    auth: &Auth,
+    is_dataset: bool,
+    cache: Option<&RepoCache>,
+) -> PyResult<Result<RepoInfo, AccessDenial>> {
+    let kind = if is_dataset { "datasets" } else { "models" };
+    let url = api_url(&config.endpoint, kind, repo_id, config.revision.as_deref());
+    let cache = cache.filter(|cache| cache.is_dataset == is_dataset);
+    let etag = cache.map(|cache| cache.etag.as_str());
+
+    Ok(match fetch_repo_json(client, config, &url, auth, etag).await? {
+        Ok(RepoJson::NotModified) => {
+            // 服务器在没有发送 ETag 时也可能返回 304，此时没有可用的缓存
+            let cache = cache.ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "Server returned 304 Not Modified for {} without a conditional request", url
+            )))?;
+            println!("Repository unchanged since the last run, using cached file list");
+            Ok(repo_info(config, repo_id, is_dataset, cache.files.clone(), cache.commit.clone(), Some(cache.etag.clone())))
+        }
+        Ok(RepoJson::Fetched(json, etag)) => {
+            warn_case_mismatch(repo_id, &json);
+            let (files, complete) = extract_files(client, config, repo_id, auth, &json, is_dataset).await?;
+            let commit = json["sha"].as_str().map(str::to_string);
+            // 有文件解析失败时不缓存，下次运行重新解析
+            Ok(repo_info(config, repo_id, is_dataset, files, commit, etag.filter(|_| complete)))
+        }
+        Err(denial) => Err(denial),
+    })
+}
 
 pub async fn get_repo_info(
     client: &Client,
     config: &Config,
     repo_id: &str,
     auth: &Auth,
+    cache: Option<&RepoCache>,
+    now: u64,
 ) -> PyResult<RepoInfo> {
+    // 缓存在 metadata_cache_ttl_secs 内验证过时直接使用，连条件请求也不发
+    if let Some(cache) = cache.filter(|cache| cache.is_fresh(config.metadata_cache_ttl_secs, now)) {
+        println!("Using the file list cached {}s ago (--refresh to revalidate)", now.saturating_sub(cache.validated_at));
+        return Ok(repo_info(config, repo_id, cache.is_dataset, cache.files.clone(), cache.commit.clone(), Some(cache.etag.clone())));
+    }
+
     // 先尝试作为 model 获取
-    let model_url = format!("{}/api/models/{}", config.endpoint, repo_id);
-    let mut request = client.get(&model_url);
-    if let Some(token) = &auth.token {
-        request = request.header("Authorization", format!("Bearer {}", token));
+    let model_denial = match fetch_repo_info(client, config, repo_id, auth, false, cache).await? {
+        Ok(info) => return Ok(info),
+        Err(denial) => denial,
+    };
+
+    // 如果不是 model，尝试作为 dataset 获取
+    let dataset_denial = match fetch_repo_info(client, config, repo_id, auth, true, cache).await? {
+        Ok(info) => return Ok(info),
+        Err(denial) => denial,
+    };
+
+    // 如果都不是，按 Hub 给出的原因区分 gated、token 无效和仓库不存在
+    let denials = [model_denial, dataset_denial];
+    if denials.contains(&AccessDenial::Gated) {
+        return Err(pyo3::exceptions::PyPermissionError::new_err(gated_message(config, repo_id)));
+    }
+    if denials.contains(&AccessDenial::InvalidToken) {
+        return Err(pyo3::exceptions::PyPermissionError::new_err(INVALID_TOKEN_MESSAGE));
     }
+    let message = if denials.iter().all(|d| *d == AccessDenial::NotFound) {
+        let hint = if auth.token.is_some() {
+            "or your token has no access to this private repository"
+        } else {
+            "or pass an access token if it's a private repository"
+        };
+        format!("Repository {} not found. Check the repository ID, {}.", repo_id, hint)
+    } else {
+        format!(
+            "Failed to get repository {} (model: {}, dataset: {}).",
+            repo_id, model_denial, dataset_denial
+        )
+    };
+    Err(pyo3::exceptions::PyRuntimeError::new_err(message))
+}
 
-    let response = request.send()
-        .await
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to get repo info: {}", e)))?;
+const INVALID_TOKEN_MESSAGE: &str =
+    "The access token is invalid or expired. Check --hf_token, HF_TOKEN or the token saved by `huggingface-cli login`.";
 
-    if response.status().is_success() {
-        let json: Value = response.json()
-            .await
-            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to parse repo info: {}", e)))?;
-        
-        let files = extract_files(client, &config.endpoint, repo_id, auth, &json, false).await?;
-        let model_endpoint = format!("{}/models/{}", config.endpoint, repo_id);
-        return Ok(RepoInfo {
-            model_endpoint: Some(model_endpoint),
-            dataset_endpoint: None,
-            files,
-        });
+fn gated_message(config: &Config, repo_id: &str) -> String {
+    format!(
+        "Repository {} is gated. Request access at {}/{} and use a token from an account that has been granted access.",
+        repo_id, config.endpoint, repo_id
+    )
+}
+
+// whoami-v2 接口返回的 token 信息
+pub struct TokenInfo {
+    pub name: String,
+    // read、write 或 fineGrained
+    pub role: Option<String>,
+    // 细粒度 token 的全局权限
+    pub scopes: Vec<String>,
+    // 细粒度 token 能否读取已获批的 gated 仓库；其他 token 为 None
+    pub can_read_gated: Option<bool>,
+}
+
+impl TokenInfo {
+    // 用于日志的权限说明，如 "fineGrained: discussion.write, post.write"
+    pub fn describe(&self) -> String {
+        let role = self.role.as_deref().unwrap_or("unknown role");
+        if self.scopes.is_empty() {
+            role.to_string()
+        } else {
+            format!("{}: {}", role, self.scopes.join(", "))
+        }
     }
+}
 
-    // 如果不是 model，尝试作为 dataset 获取
-    let dataset_url = format!("{}/api/datasets/{}", config.endpoint, repo_id);
-    let mut request = client.get(&dataset_url);
-    if let Some(token) = &auth.token {
-        request = request.header("Authorization", format!("Bearer {}", token));
+// 用 whoami-v2 接口确认 token 有效，401 视为 token 无效或已过期
+pub async fn whoami(client: &Client, config: &Config, token: &str, timeout: std::time::Duration) -> Result<TokenInfo, String> {
+    let response = client.get(format!("{}/api/whoami-v2", config.endpoint))
+        .header("Authorization", format!("Bearer {}", token))
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| format!("failed to check token: {}", e))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(format!("token is invalid or expired ({})", status));
+    }
+    if !status.is_success() {
+        return Err(format!("token was rejected: {}", status));
     }
 
-    let response = request.send()
+    let json = response.json::<Value>()
         .await
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to get repo info: {}", e)))?;
+        .map_err(|e| format!("failed to check token: {}", e))?;
+    let access_token = &json["auth"]["accessToken"];
+    let fine_grained = &access_token["fineGrained"];
+    Ok(TokenInfo {
+        name: json["name"].as_str().unwrap_or("unknown user").to_string(),
+        role: access_token["role"].as_str().map(String::from),
+        scopes: fine_grained["global"].as_array()
+            .map(|scopes| scopes.iter().filter_map(|s| s.as_str().map(String::from)).collect())
+            .unwrap_or_default(),
+        can_read_gated: fine_grained["canReadGatedRepos"].as_bool(),
+    })
+}
 
-    if response.status().is_success() {
-        let json: Value = response.json()
-            .await
-            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to parse repo info: {}", e)))?;
-        
-        let files = extract_files(client, &config.endpoint, repo_id, auth, &json, true).await?;
-        let dataset_endpoint = format!("{}/datasets/{}", config.endpoint, repo_id);
-        return Ok(RepoInfo {
-            model_endpoint: None,
-            dataset_endpoint: Some(dataset_endpoint),
-            files,
-        });
+// 只确认仓库是否存在（不解析文件），返回仓库类型；不存在或无权访问时返回 None
+pub async fn find_repo(client: &Client, config: &Config, repo_id: &str, auth: &Auth) -> PyResult<Option<&'static str>> {
+    for kind in ["models", "datasets"] {
+        let url = api_url(&config.endpoint, kind, repo_id, config.revision.as_deref());
+        if fetch_repo_json(client, config, &url, auth, None).await?.is_ok() {
+            return Ok(Some(if kind == "models" { "model" } else { "dataset" }));
+        }
     }
-
-    // 如果都不是，返回错误
-    Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
-        "Repository {} not found or unauthorized. Please check the repository ID and your access token if it's a private repository.",
-        repo_id
-    )))
+    Ok(None)
 }
 
 async fn extract_files(
     client: &Client,
-    endpoint: &str,
+    config: &Config,
     repo_id: &str,
     auth: &Auth,
     json: &Value,
     is_dataset: bool,
-) -> PyResult<Vec<FileInfo>> {
-    let siblings = json["siblings"].as_array()
-        .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("No files found in repository"))?;
-    
-    // 使用信号量限制并发数
-    let semaphore = Arc::new(Semaphore::new(10));
-    let client = Arc::new(client.clone());
-    let auth = Arc::new(auth.clone());
-
-    let mut tasks = Vec::new();
-    for file in siblings {
-        if let Some(rfilename) = file["rfilename"].as_str() {
-            let client = client.clone();
-            let auth = auth.clone();
-            let semaphore = semaphore.clone();
-            let rfilename = rfilename.to_string();
-            let endpoint = endpoint.to_string();
-            let repo_id = repo_id.to_string();
-
-            tasks.push(tokio::spawn(async move {
-                let _permit = semaphore.acquire().await.unwrap();
-                resolve_file_info(&client, &endpoint, &repo_id, &rfilename, &auth, is_dataset).await
-            }));
-        }
-    }
-
-    let results = join_all(tasks).await;
+) -> PyResult<(Vec<FileInfo>, bool)> {
+    let mut siblings = json["siblings"].as_array().cloned().unwrap_or_default();
+
+    // 大仓库的 siblings 可能不完整或缺少文件大小，此时改用分页的 tree 接口补全
+    if siblings.is_empty() || siblings.iter().any(|file| file["size"].as_u64().is_none()) {
+        match fetch_tree(client, config, repo_id, auth, is_dataset).await {
+            Ok(tree) => merge_tree(&mut siblings, tree),
+            Err(e) if !siblings.is_empty() => {
+                crate::warnings::warn(format!("Failed to list repository tree, using repo info only: {}", e));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    // 目录标记等不是文件的条目跳过，否则会在目录的位置创建同名文件
+    siblings.retain(|file| match file["rfilename"].as_str() {
+        Some(rfilename) if !is_file_entry(rfilename) => {
+            crate::warnings::warn(format!("Skipping '{}': not a file path", rfilename));
+            false
+        }
+        _ => true,
+    });
+    if siblings.is_empty() {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err("No files found in repository"));
+    }
+    let siblings = &siblings;
+
+    // 上次中断时已解析的文件直接复用，只对其余文件发 HEAD 请求
+    let cache_path = resolve_cache_path(config, repo_id, is_dataset);
+    let cached = load_resolve_cache(&cache_path);
+    let mut cache_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&cache_path)
+        .ok();
+
+    // 以流的方式解析，同时最多 10 个请求，文件数很多时不会预先创建所有任务；保持原有顺序
+    let cached = &cached;
+    let mut results = futures::stream::iter(siblings.iter().filter_map(|file| {
+        let rfilename = file["rfilename"].as_str()?.to_string();
+        // blobs=true 时 siblings 带有 git blob id，LFS 文件还带有 sha256
+        let sha256 = file["lfs"]["sha256"].as_str().map(String::from);
+        let blob_id = file["blobId"].as_str().map(String::from);
+        let listed_size = file["size"].as_u64();
+        // LFS 文件的实际大小，HEAD 没有跟随重定向时得到的可能是指针的大小
+        let lfs_size = file["lfs"]["size"].as_u64();
+        Some((rfilename, sha256, blob_id, listed_size, lfs_size))
+    }))
+        .map(|(rfilename, sha256, blob_id, listed_size, lfs_size)| async move {
+            // 文件内容没变（blob id 相同）时使用缓存的解析结果
+            if let Some(info) = cached.get(&rfilename).filter(|info| info.blob_id == blob_id) {
+                return Ok((info.clone(), false));
+            }
+            resolve_file_info(client, config, repo_id, &rfilename, auth, is_dataset)
+                .await
+                .map(|info| {
+                    // LFS 文件以 lfs.size 为准；HEAD 没有给出大小时使用文件列表中的大小
+                    let size = lfs_size.or(info.size).or(listed_size);
+                    (FileInfo { size, sha256, blob_id, ..info }, true)
+                })
+                .map_err(|e| (rfilename, e))
+        })
+        .buffered(10);
+
     let mut files = Vec::new();
-    for result in results {
-        if let Ok(Ok(file_info)) = result {
-            files.push(file_info);
+    let mut complete = true;
+    while let Some(result) = results.next().await {
+        match result {
+            Ok((file_info, resolved)) => {
+                if let (true, Some(cache_file)) = (resolved, cache_file.as_mut()) {
+                    if let Ok(line) = serde_json::to_string(&file_info) {
+                        let _ = writeln!(cache_file, "{}", line);
+                    }
+                }
+                files.push(file_info);
+            }
+            Err((rfilename, e)) => {
+                complete = false;
+                crate::warnings::warn(format!("Skipping {}: {}", rfilename, e));
+            }
         }
     }
 
+    // 解析阶段完成后不再需要缓存
+    drop(cache_file);
+    let _ = std::fs::remove_file(&cache_path);
+
+    Ok((files, complete))
+}
+
+// 以 `/` 结尾的目录标记，以及空路径、`.` 这类指向仓库根目录本身的条目都不是文件
+fn is_file_entry(rfilename: &str) -> bool {
+    !rfilename.ends_with('/')
+        && !rfilename.ends_with('\\')
+        && std::path::Path::new(rfilename)
+            .components()
+            .any(|component| !matches!(component, std::path::Component::CurDir))
+}
+
+// 逐页请求 tree 接口（recursive=true），按 Link 头中 rel="next" 的地址翻页直到结束，
+// 把文件条目转换为与 siblings 相同的形状（rfilename、size、blobId、lfs.sha256）
+async fn fetch_tree(
+    client: &Client,
+    config: &Config,
+    repo_id: &str,
+    auth: &Auth,
+    is_dataset: bool,
+) -> PyResult<Vec<Value>> {
+    let kind = if is_dataset { "datasets" } else { "models" };
+    let revision = encode_revision(config.revision.as_deref().unwrap_or("main"));
+    let mut next = Some(format!("{}/api/{}/{}/tree/{}?recursive=true", config.endpoint, kind, repo_id, revision));
+
+    let mut files = Vec::new();
+    while let Some(url) = next.take() {
+        let mut request = client.get(&url);
+        if let Some(token) = &auth.token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let response = request.send()
+            .await
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to list repository tree: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "Failed to list repository tree: {}", response.status()
+            )));
+        }
+        next = next_page(response.headers());
+
+        let page: Vec<Value> = response.json()
+            .await
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to parse repository tree: {}", e)))?;
+        files.extend(page.into_iter()
+            .filter(|entry| entry["type"] == "file")
+            .map(|entry| serde_json::json!({
+                "rfilename": entry["path"],
+                "size": entry["size"],
+                "blobId": entry["oid"],
+                "lfs": { "sha256": entry["lfs"]["oid"] },
+            })));
+    }
     Ok(files)
 }
 
+// Link: <https://...>; rel="next"
+fn next_page(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let link = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        params.contains("rel=\"next\"")
+            .then(|| target.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+// tree 中的条目覆盖 siblings 中的同名条目（带有大小），siblings 中没有的文件追加在后面
+fn merge_tree(siblings: &mut Vec<Value>, tree: Vec<Value>) {
+    let mut index: HashMap<String, usize> = siblings.iter()
+        .enumerate()
+        .filter_map(|(i, file)| Some((file["rfilename"].as_str()?.to_string(), i)))
+        .collect();
+    for entry in tree {
+        let Some(rfilename) = entry["rfilename"].as_str().map(String::from) else { continue };
+        match index.get(&rfilename) {
+            Some(&i) => siblings[i] = entry,
+            None => {
+                index.insert(rfilename, siblings.len());
+                siblings.push(entry);
+            }
+        }
+    }
+}
+
+// 解析进度缓存位于临时目录，按 endpoint、仓库、类型和 revision 区分
+fn resolve_cache_path(config: &Config, repo_id: &str, is_dataset: bool) -> PathBuf {
+    let key = format!(
+        "{}-{}-{}-{}",
+        config.endpoint,
+        if is_dataset { "datasets" } else { "models" },
+        repo_id,
+        config.revision.as_deref().unwrap_or("main"),
+    );
+    let key: String = key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    std::env::temp_dir().join(format!("hfd-resolve-{}.jsonl", key))
+}
+
+// 每行一个已解析的 FileInfo，中断时写了一半的最后一行会被忽略
+fn load_resolve_cache(path: &Path) -> HashMap<String, FileInfo> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<FileInfo>(line).ok())
+        .map(|info| (info.rfilename.clone(), info))
+        .collect()
+}
+
 async fn resolve_file_info(
     client: &Client,
-    endpoint: &str,
+    config: &Config,
     repo_id: &str,
     rfilename: &str,
     auth: &Auth,
     is_dataset: bool,
 ) -> PyResult<FileInfo> {
-    let url = if is_dataset {
-        format!("{}/datasets/{}/resolve/main/{}", endpoint, repo_id, rfilename)
-    } else {
-        format!("{}/{}/resolve/main/{}", endpoint, repo_id, rfilename)
-    };
+    let response = head_file(client, config, repo_id, rfilename, auth, is_dataset).await?;
+    if AccessDenial::from_response(response.status(), response.headers(), None) == AccessDenial::Gated {
+        return Err(pyo3::exceptions::PyPermissionError::new_err(gated_message(config, repo_id)));
+    }
+    if !response.status().is_success() {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to resolve file: HTTP {}", response.status())));
+    }
+    Ok(file_info_from(rfilename, &response))
+}
+
+// 不列出仓库，只用 HEAD 请求确认单个文件；先按 model 再按 dataset 查找，
+// 返回文件信息和是否为数据集。文件不存在时返回 None，
+// 鉴权失败和重试后仍失败的服务器错误作为错误返回
+pub async fn probe_file(
+    client: &Client,
+    config: &Config,
+    repo_id: &str,
+    rfilename: &str,
+    auth: &Auth,
+) -> PyResult<Option<(FileInfo, bool)>> {
+    let mut failures = Vec::new();
+    let mut missing = false;
+    for is_dataset in [false, true] {
+        let response = head_file(client, config, repo_id, rfilename, auth, is_dataset).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some((file_info_from(rfilename, &response), is_dataset)));
+        }
+        if status == reqwest::StatusCode::NOT_FOUND {
+            missing = true;
+            continue;
+        }
+        match AccessDenial::from_response(status, response.headers(), None) {
+            AccessDenial::Gated => {
+                return Err(pyo3::exceptions::PyPermissionError::new_err(gated_message(config, repo_id)));
+            }
+            AccessDenial::InvalidToken => {
+                return Err(pyo3::exceptions::PyPermissionError::new_err(INVALID_TOKEN_MESSAGE));
+            }
+            _ => failures.push(status),
+        }
+    }
 
-    let mut request = client.head(&url);
-    if let Some(token) = &auth.token {
-        request = request.header("Authorization", format!("Bearer {}", token));
+    // 匿名访问不存在的仓库时 Hub 返回 401，另一种类型的仓库返回 404 时按文件不存在处理
+    let denied = failures.iter().all(|s| matches!(s.as_u16(), 401 | 403));
+    let Some(status) = failures.first().filter(|_| !(denied && missing)) else {
+        return Ok(None);
+    };
+    if denied {
+        let hint = if auth.token.is_some() {
+            "your token has no access to this repository"
+        } else {
+            "pass an access token if it's a private repository"
+        };
+        return Err(pyo3::exceptions::PyPermissionError::new_err(format!(
+            "Access to {} in repository {} was denied (HTTP {}). Check the repository ID, or {}.",
+            rfilename, repo_id, status.as_u16(), hint
+        )));
     }
+    Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+        "Failed to resolve file {} in repository {}: HTTP {}",
+        rfilename, repo_id, status
+    )))
+}
 
-    let response = request.send()
-        .await
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to resolve file: {}", e)))?;
+async fn head_file(
+    client: &Client,
+    config: &Config,
+    repo_id: &str,
+    rfilename: &str,
+    auth: &Auth,
+    is_dataset: bool,
+) -> PyResult<reqwest::Response> {
+    let url = resolve_url(&config.endpoint, repo_id, config.revision.as_deref(), rfilename, is_dataset);
 
+    // HEAD 请求单独按指数退避重试 size_probe_retries 次，避免偶发失败导致文件大小未知；401/404 不重试
+    let mut attempt = 0;
+    loop {
+        let mut request = client.head(&url);
+        if let Some(token) = &auth.token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let result = request.send().await;
+        let retryable = match &result {
+            Ok(response) => is_retryable_status(response.status(), config),
+            Err(_) => true,
+        };
+        if !retryable || attempt >= config.size_probe_retries {
+            return result.map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to resolve file: {}", e)));
+        }
+        attempt += 1;
+        tokio::time::sleep(backoff_delay(attempt)).await;
+    }
+}
+
+fn file_info_from(rfilename: &str, response: &reqwest::Response) -> FileInfo {
+    // 未跟随重定向时 X-Linked-Size 是 LFS 文件的实际大小，Content-Length 只是重定向响应的长度
     let size = response.headers()
-        .get("content-length")
+        .get("x-linked-size")
+        .or_else(|| response.headers().get("content-length"))
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.parse::<u64>().ok());
 
-    Ok(FileInfo {
+    let last_modified = response.headers()
+        .get("last-modified")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    FileInfo {
         rfilename: rfilename.to_string(),
         size,
-    })
-} 
\ No newline at end of file
+        sha256: None,
+        blob_id: None,
+        last_modified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_url_encodes_the_revision() {
+        let endpoint = "https://hf.example";
+        assert_eq!(resolve_url(endpoint, "org/model", None, "config.json", false), "https://hf.example/org/model/resolve/main/config.json");
+        assert_eq!(
+            resolve_url(endpoint, "org/model", Some("refs/pr/1"), "sub/model.bin", false),
+            "https://hf.example/org/model/resolve/refs%2Fpr%2F1/sub/model.bin"
+        );
+        assert_eq!(
+            resolve_url(endpoint, "org/data", Some("v2"), "train.parquet", true),
+            "https://hf.example/datasets/org/data/resolve/v2/train.parquet"
+        );
+    }
+
+    // 每个文件的第一次 HEAD 返回 503
+    fn flaky_head_server(repo: crate::testing::MockRepo) -> crate::testing::MockServer {
+        use crate::testing::{MockServer, Response};
+        use std::collections::HashSet;
+        use std::sync::Mutex;
+
+        let failed = Mutex::new(HashSet::new());
+        MockServer::start(move |request| {
+            if request.method == "HEAD" && failed.lock().unwrap().insert(request.path.clone()) {
+                return Response::new(503);
+            }
+            repo.respond(request).unwrap_or_else(Response::not_found)
+        })
+    }
+
+    #[tokio::test]
+    async fn size_probe_is_retried_after_a_failed_head() {
+        use crate::testing::{self, MockRepo};
+
+        let server = flaky_head_server(MockRepo::new("org/flaky", &[("model.bin", b"0123456789")]));
+        let auth = Auth { token: None };
+        let client = Client::new();
+
+        let config = testing::config(&server.url());
+        assert!(resolve_file_info(&client, &config, "org/flaky", "model.bin", &auth, false).await.is_err());
+
+        let server = flaky_head_server(MockRepo::new("org/flaky", &[("model.bin", b"0123456789")]));
+        let mut config = testing::config(&server.url());
+        config.size_probe_retries = 1;
+        let info = resolve_file_info(&client, &config, "org/flaky", "model.bin", &auth, false).await.unwrap();
+        assert_eq!(info.size, Some(10));
+        assert_eq!(server.requests().iter().filter(|r| r.method == "HEAD").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn probe_reports_server_and_auth_errors() {
+        use crate::testing::{self, MockServer, Response};
+
+        let auth = Auth { token: None };
+        let client = Client::new();
+
+        let server = MockServer::start(|_| Response::new(503));
+        let config = testing::config(&server.url());
+        let err = probe_file(&client, &config, "org/model", "model.bin", &auth).await.unwrap_err();
+        assert!(testing::error_message(err).contains("HTTP 503"));
+
+        let server = MockServer::start(|_| Response::new(401));
+        let config = testing::config(&server.url());
+        let err = probe_file(&client, &config, "org/model", "model.bin", &auth).await.unwrap_err();
+        assert!(testing::error_message(err).contains("denied (HTTP 401)"));
+
+        // 数据集返回 404 时，模型的 401 按仓库不存在处理
+        let server = MockServer::start(|request| if request.path.starts_with("/datasets/") {
+            Response::not_found()
+        } else {
+            Response::new(401)
+        });
+        let config = testing::config(&server.url());
+        assert!(probe_file(&client, &config, "org/model", "model.bin", &auth).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn interrupted_resolution_resumes_from_the_cache() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let names: Vec<String> = (0..20).map(|i| format!("f{:02}.bin", i)).collect();
+        let files: Vec<(&str, &[u8])> = names.iter().map(|name| (name.as_str(), name.as_bytes())).collect();
+        let repo = MockRepo::new("org/huge", &files);
+        let json = repo.info();
+
+        // 第一次运行时 f10.bin 的 HEAD 挂起，在它之前的文件已解析完成
+        let stalled = Arc::new(AtomicBool::new(true));
+        let server = {
+            let stalled = stalled.clone();
+            MockServer::start(move |request| {
+                let response = repo.respond(request).unwrap_or_else(Response::not_found);
+                if stalled.load(Ordering::SeqCst) && request.path.ends_with("/f10.bin") {
+                    return response.delayed(Duration::from_secs(3));
+                }
+                response
+            })
+        };
+        let heads = || server.requests().iter().filter(|r| r.method == "HEAD").count();
+        let config = testing::config(&server.url());
+        let client = Client::new();
+        let auth = Auth { token: None };
+
+        let interrupted = extract_files(&client, &config, "org/huge", &auth, &json, false);
+        assert!(tokio::time::timeout(Duration::from_millis(1000), interrupted).await.is_err());
+        let first = heads();
+
+        // 重新运行只对没有解析完的文件发 HEAD
+        stalled.store(false, Ordering::SeqCst);
+        let (resolved, complete) = extract_files(&client, &config, "org/huge", &auth, &json, false).await.unwrap();
+        assert!(complete);
+        assert_eq!(resolved.iter().map(|f| f.rfilename.as_str()).collect::<Vec<_>>(), names);
+        assert_eq!(heads() - first, 10);
+        assert!(!resolve_cache_path(&config, "org/huge", false).exists());
+    }
+
+    #[test]
+    fn normalize_repo_id_trims_slashes_but_keeps_case() {
+        assert_eq!(normalize_repo_id("Org/Repo/"), "Org/Repo");
+        assert_eq!(normalize_repo_id(" /gpt2// "), "gpt2");
+        assert_eq!(normalize_repo_id("org/repo"), "org/repo");
+    }
+
+    #[tokio::test]
+    async fn trailing_slash_and_case_mismatch() {
+        use crate::testing::{self, MockRepo, MockServer, Request, Response};
+
+        // Hub 对仓库名不区分大小写地查找，返回规范的名字
+        let repo = MockRepo::new("org/repo", &[("config.json", b"{}")]);
+        let server = MockServer::start(move |request| {
+            let request = Request { path: request.path.to_lowercase(), ..request.clone() };
+            repo.respond(&request).unwrap_or_else(Response::not_found)
+        });
+        let dir = tempfile::tempdir().unwrap();
+
+        let (path, _) = testing::download(testing::config(&server.url()), "org/repo/", dir.path()).await.unwrap();
+        assert_eq!(Path::new(&path), dir.path().join("org/repo"));
+        assert!(server.requests().iter().all(|r| !r.path.contains("repo//") && !r.path.contains("repo/?")));
+        assert!(!crate::warnings::recorded().iter().any(|w| w.starts_with("Repository org/repo resolved")));
+
+        // 名字按用户给出的使用，同时提示大小写不同
+        let (path, _) = testing::download(testing::config(&server.url()), "Org/Repo", dir.path()).await.unwrap();
+        assert_eq!(Path::new(&path), dir.path().join("Org/Repo"));
+        assert!(crate::warnings::recorded().iter().any(|w| w.starts_with("Repository Org/Repo resolved to org/repo")));
+    }
+
+    #[tokio::test]
+    async fn truncated_repo_info_is_retried() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // 第一次仓库信息响应只发送一半就断开
+        let repo = MockRepo::new("org/cut", &[("config.json", b"{}"), ("model.bin", b"weights")]);
+        let truncated = AtomicBool::new(false);
+        let server = MockServer::start(move |request| {
+            let response = repo.respond(request).unwrap_or_else(Response::not_found);
+            if request.path.starts_with("/api/") && !truncated.swap(true, Ordering::SeqCst) {
+                let half = response.body.len() / 2;
+                return response.truncated(half);
+            }
+            response
+        });
+        let dir = tempfile::tempdir().unwrap();
+
+        let (path, _) = testing::download(testing::config(&server.url()), "org/cut", dir.path()).await.unwrap();
+        let api_requests = server.requests().iter().filter(|r| r.path.starts_with("/api/models/org/cut")).count();
+        assert_eq!(api_requests, 2);
+        assert_eq!(std::fs::read(Path::new(&path).join("model.bin")).unwrap(), b"weights");
+        assert_eq!(std::fs::read(Path::new(&path).join("config.json")).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn directory_markers_are_not_file_entries() {
+        for rfilename in ["config.json", "sub/model.bin", "./a.txt", ".gitattributes"] {
+            assert!(is_file_entry(rfilename), "{}", rfilename);
+        }
+        for rfilename in ["", ".", "./", "sub/", "sub\\"] {
+            assert!(!is_file_entry(rfilename), "{:?}", rfilename);
+        }
+    }
+
+    #[test]
+    fn next_page_follows_the_next_link() {
+        use reqwest::header::{HeaderMap, HeaderValue, LINK};
+
+        let link = |value: &str| HeaderMap::from_iter([(LINK, HeaderValue::from_str(value).unwrap())]);
+        assert_eq!(
+            next_page(&link(r#"<https://hf.example/api/models/org/m/tree/main?cursor=abc>; rel="next""#)).as_deref(),
+            Some("https://hf.example/api/models/org/m/tree/main?cursor=abc")
+        );
+        assert_eq!(
+            next_page(&link(r#"<https://hf.example/first>; rel="prev", <https://hf.example/second>; rel="next""#)).as_deref(),
+            Some("https://hf.example/second")
+        );
+        assert_eq!(next_page(&link(r#"<https://hf.example/first>; rel="prev""#)), None);
+        assert_eq!(next_page(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn merge_tree_fills_sizes_and_appends_missing_files() {
+        let mut siblings = vec![
+            serde_json::json!({ "rfilename": "config.json" }),
+            serde_json::json!({ "rfilename": "model.bin" }),
+        ];
+        merge_tree(&mut siblings, vec![
+            serde_json::json!({ "rfilename": "model.bin", "size": 10 }),
+            serde_json::json!({ "rfilename": "extra/data.bin", "size": 5 }),
+            serde_json::json!({ "size": 1 }),
+        ]);
+        let names: Vec<_> = siblings.iter().map(|file| file["rfilename"].as_str().unwrap()).collect();
+        assert_eq!(names, ["config.json", "model.bin", "extra/data.bin"]);
+        assert!(siblings[0]["size"].is_null());
+        assert_eq!(siblings[1]["size"], 10);
+        assert_eq!(siblings[2]["size"], 5);
+    }
+
+    #[tokio::test]
+    async fn directory_marker_sibling_is_skipped() {
+        use crate::testing::{self, MockRepo};
+
+        let server = MockRepo::new("org/marker", &[("weights/", b""), ("weights/a.bin", b"abc")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        testing::download(testing::config(&server.url()), "org/marker", dir.path()).await.unwrap();
+
+        assert_eq!(server.downloads(), ["weights/a.bin"]);
+        assert!(dir.path().join("org/marker/weights").is_dir());
+        assert_eq!(std::fs::read(dir.path().join("org/marker/weights/a.bin")).unwrap(), b"abc");
+        assert!(crate::warnings::recorded().iter().any(|warning| warning == "Skipping 'weights/': not a file path"));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::types::FileInfo;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// 下载目录中缓存的仓库文件列表。重新运行时带上 ETag 请求仓库信息，
+// 服务器返回 304 时直接使用这里的文件信息，不再逐个发 HEAD 请求
+pub const REPO_CACHE_FILE: &str = ".hfd-manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoCache {
+    pub repo_id: String,
+    pub is_dataset: bool,
+    #[serde(default)]
+    pub revision: Option<String>,
+    pub etag: String,
+    #[serde(default)]
+    pub commit: Option<String>,
+    // 过滤前的完整文件列表
+    pub files: Vec<FileInfo>,
+    // 最近一次由服务器确认文件列表的时间（Unix 秒）
+    #[serde(default)]
+    pub validated_at: u64,
+}
+
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl RepoCache {
+    // 只使用同一仓库、同一 revision 的缓存；文件损坏时当作没有缓存
+    pub fn load(dir: &Path, repo_id: &str, revision: Option<&str>) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join(REPO_CACHE_FILE)).ok()?;
+        let cache: Self = serde_json::from_str(&content).ok()?;
+        (cache.repo_id == repo_id && cache.revision.as_deref() == revision).then_some(cache)
+    }
+
+    // 到 now（Unix 秒）为止在 ttl_secs 内验证过的缓存可以不经请求直接使用；ttl 为 0 时总是重新验证
+    pub fn is_fresh(&self, ttl_secs: u64, now: u64) -> bool {
+        now.saturating_sub(self.validated_at) < ttl_secs
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize repo cache: {}", e))?;
+        std::fs::write(dir.join(REPO_CACHE_FILE), content)
+            .map_err(|e| format!("Failed to write {}: {}", REPO_CACHE_FILE, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[test]
+    fn fresh_only_within_the_ttl() {
+        let cache = RepoCache {
+            repo_id: "org/m".to_string(),
+            is_dataset: false,
+            revision: None,
+            etag: "\"abc\"".to_string(),
+            commit: None,
+            files: Vec::new(),
+            validated_at: 1_000,
+        };
+        assert!(cache.is_fresh(300, 1_000));
+        assert!(cache.is_fresh(300, 1_299));
+        assert!(!cache.is_fresh(300, 1_300));
+        assert!(!cache.is_fresh(0, 1_000));
+        // 时钟回拨时仍视为刚验证过
+        assert!(cache.is_fresh(300, 900));
+    }
+
+    #[tokio::test]
+    async fn request_is_skipped_within_ttl_and_conditional_after() {
+        let server = MockRepo::new("org/cached", &[("config.json", b"{}")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/cached");
+        let mut config = testing::config(&server.url());
+        config.metadata_cache_ttl_secs = 300;
+        let api_requests = || server.requests().into_iter().filter(|r| r.path.starts_with("/api/")).collect::<Vec<_>>();
+
+        testing::download(config.clone(), "org/cached", dir.path()).await.unwrap();
+        let first = api_requests();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].header("if-none-match"), None);
+        let cache = RepoCache::load(&target, "org/cached", None).unwrap();
+        let requests_after_first = server.requests().len();
+
+        // TTL 内不发任何请求
+        testing::download(config.clone(), "org/cached", dir.path()).await.unwrap();
+        assert_eq!(server.requests().len(), requests_after_first);
+
+        // 过期后带 ETag 发条件请求，304 时沿用缓存的文件列表
+        RepoCache { validated_at: unix_now() - 600, ..cache.clone() }.save(&target).unwrap();
+        testing::download(config, "org/cached", dir.path()).await.unwrap();
+        let requests = api_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("if-none-match"), Some(cache.etag.as_str()));
+        assert_eq!(server.downloads(), ["config.json"]);
+        // 验证后重新计时
+        assert!(RepoCache::load(&target, "org/cached", None).unwrap().is_fresh(300, unix_now()));
+    }
+}
@@ -0,0 +1,178 @@
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const FAMILY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// 类似 happy eyeballs 的解析器：首选地址族在 fallback_delay 内连不上时，
+// 立即尝试另一地址族，把先连通的地址族排在最前面交给连接器
+pub struct HappyEyeballsResolver {
+    fallback_delay: Duration,
+    probe_port: u16,
+    // host -> (IPv6 是否胜出, 记录时间)
+    family_cache: Arc<Mutex<HashMap<String, (bool, Instant)>>>,
+}
+
+impl HappyEyeballsResolver {
+    pub fn new(fallback_delay: Duration, probe_port: u16) -> Self {
+        Self {
+            fallback_delay,
+            probe_port,
+            family_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Resolve for HappyEyeballsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let fallback_delay = self.fallback_delay;
+        let probe_port = self.probe_port;
+        let family_cache = self.family_cache.clone();
+
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+
+            let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter().partition(|a| a.is_ipv6());
+            if v6.is_empty() || v4.is_empty() {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+
+            let cached = {
+                let cache = family_cache.lock().unwrap();
+                cache.get(&host)
+                    .filter(|(_, at)| at.elapsed() < FAMILY_CACHE_TTL)
+                    .map(|(ipv6, _)| *ipv6)
+            };
+
+            let ipv6_wins = match cached {
+                Some(ipv6) => ipv6,
+                None => {
+                    // 以系统解析结果的第一个地址族为首选
+                    let prefer_ipv6 = addrs[0].is_ipv6();
+                    let (preferred, fallback) = if prefer_ipv6 { (v6[0], v4[0]) } else { (v4[0], v6[0]) };
+                    let preferred_wins = race_families(probe(preferred, probe_port), || probe(fallback, probe_port), fallback_delay).await;
+                    let ipv6 = prefer_ipv6 == preferred_wins;
+                    family_cache.lock().unwrap().insert(host, (ipv6, Instant::now()));
+                    ipv6
+                }
+            };
+
+            let ordered: Vec<SocketAddr> = if ipv6_wins {
+                v6.into_iter().chain(v4).collect()
+            } else {
+                v4.into_iter().chain(v6).collect()
+            };
+            Ok(Box::new(ordered.into_iter()) as Addrs)
+        })
+    }
+}
+
+async fn probe(mut addr: SocketAddr, port: u16) -> bool {
+    addr.set_port(port);
+    matches!(tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await, Ok(Ok(_)))
+}
+
+// 返回 true 表示首选地址族胜出；fallback 在 fallback_delay 后首选地址族仍未连通时才开始
+async fn race_families<P, F, Fut>(preferred_probe: P, fallback: F, fallback_delay: Duration) -> bool
+where
+    P: std::future::Future<Output = bool>,
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    tokio::pin!(preferred_probe);
+
+    tokio::select! {
+        ok = &mut preferred_probe => return ok,
+        _ = tokio::time::sleep(fallback_delay) => {}
+    }
+
+    // 首选地址族迟迟连不上，同时尝试另一地址族
+    let fallback_probe = fallback();
+    tokio::pin!(fallback_probe);
+
+    let mut preferred_failed = false;
+    let mut fallback_failed = false;
+    while !(preferred_failed && fallback_failed) {
+        tokio::select! {
+            ok = &mut preferred_probe, if !preferred_failed => {
+                if ok {
+                    return true;
+                }
+                preferred_failed = true;
+            }
+            ok = &mut fallback_probe, if !fallback_failed => {
+                if ok {
+                    return false;
+                }
+                fallback_failed = true;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // 在 after 之后返回 ok 的探测
+    async fn answer_after(after: Duration, ok: bool) -> bool {
+        tokio::time::sleep(after).await;
+        ok
+    }
+
+    #[tokio::test]
+    async fn fallback_starts_after_the_configured_delay() {
+        let started = Instant::now();
+        let fallback_started = Arc::new(Mutex::new(None));
+        let record = fallback_started.clone();
+        let preferred_wins = race_families(
+            answer_after(Duration::from_secs(5), true),
+            move || {
+                *record.lock().unwrap() = Some(started.elapsed());
+                answer_after(Duration::ZERO, true)
+            },
+            Duration::from_millis(100),
+        ).await;
+
+        assert!(!preferred_wins);
+        let delay = fallback_started.lock().unwrap().unwrap();
+        assert!(delay >= Duration::from_millis(100), "{:?}", delay);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn fast_preferred_family_never_starts_the_fallback() {
+        let fallback_started = Arc::new(AtomicBool::new(false));
+        let record = fallback_started.clone();
+        let preferred_wins = race_families(
+            answer_after(Duration::from_millis(10), true),
+            move || {
+                record.store(true, Ordering::SeqCst);
+                answer_after(Duration::ZERO, true)
+            },
+            Duration::from_millis(500),
+        ).await;
+
+        assert!(preferred_wins);
+        assert!(!fallback_started.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn preferred_family_wins_when_both_fail() {
+        let preferred_wins = race_families(
+            answer_after(Duration::from_millis(50), false),
+            || answer_after(Duration::ZERO, false),
+            Duration::from_millis(10),
+        ).await;
+        assert!(preferred_wins);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::types::FileInfo;
+use super::memory::BufferReservation;
+use futures::future::BoxFuture;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+// 下载数据的写入目标（本地文件、对象存储的分段上传等）。
+// 分块下载时多个任务会并发调用 write_at，实现需要按偏移组装；数据全部写完后调用一次 finalize
+pub trait DownloadSink: Send + Sync {
+    fn write_at<'a>(&'a self, offset: u64, bytes: &'a [u8]) -> BoxFuture<'a, Result<(), String>>;
+    fn finalize(&self) -> BoxFuture<'_, Result<(), String>>;
+}
+
+// 为每个待下载文件创建写入目标。是否已下载、续传仍按本地文件判断，
+// 因此自定义写入目标总是从头下载整个文件
+pub trait SinkProvider: Send + Sync {
+    fn open<'a>(&'a self, file: &'a FileInfo) -> BoxFuture<'a, Result<Arc<dyn DownloadSink>, String>>;
+}
+
+// 默认的写入目标：本地文件
+pub struct LocalFileSink {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl LocalFileSink {
+    // truncate 为 false 时保留已有内容，用于续传
+    pub async fn open(path: &Path, truncate: bool) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(truncate)
+            .write(true)
+            .open(path)
+            .await
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl DownloadSink for LocalFileSink {
+    fn write_at<'a>(&'a self, offset: u64, bytes: &'a [u8]) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut file = self.file.lock().await;
+            file.seek(SeekFrom::Start(offset))
+                .await
+                .map_err(|e| format!("Failed to seek: {}", e))?;
+            file.write_all(bytes)
+                .await
+                .map_err(|e| format!("Failed to write: {}", e))?;
+            // 等待写入真正提交，之后记录块完成才可靠
+            file.flush()
+                .await
+                .map_err(|e| format!("Failed to write: {}", e))
+        })
+    }
+
+    fn finalize(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            self.file.lock()
+                .await
+                .flush()
+                .await
+                .map_err(|e| format!("Failed to write file: {}", e))
+        })
+    }
+}
+
+// 顺序写入时先在内存中攒够 capacity（buffer_size）字节再交给写入目标，减少小块写入。
+// 缓冲区占用不超过 capacity，超过 capacity 的单次写入直接交给写入目标
+pub struct BufferedWriter {
+    sink: Arc<dyn DownloadSink>,
+    offset: u64,
+    buffer: Vec<u8>,
+    capacity: usize,
+    _reservation: Option<BufferReservation>,
+}
+
+impl BufferedWriter {
+    pub fn new(sink: Arc<dyn DownloadSink>, offset: u64, capacity: usize) -> Self {
+        Self {
+            sink,
+            offset,
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            _reservation: None,
+        }
+    }
+
+    // 缓冲区占用的内存预算，写入器释放时归还
+    pub fn with_reservation(mut self, reservation: BufferReservation) -> Self {
+        self._reservation = Some(reservation);
+        self
+    }
+
+    pub async fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if self.buffer.len() + bytes.len() > self.capacity {
+            self.flush().await?;
+        }
+        if bytes.len() >= self.capacity {
+            self.sink.write_at(self.offset, bytes).await?;
+            self.offset += bytes.len() as u64;
+            return Ok(());
+        }
+        self.buffer.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), String> {
+        if !self.buffer.is_empty() {
+            self.sink.write_at(self.offset, &self.buffer).await?;
+            self.offset += self.buffer.len() as u64;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    // 按文件名保存在内存中的写入目标
+    #[derive(Default)]
+    struct MemoryStore {
+        files: std::sync::Mutex<HashMap<String, Vec<u8>>>,
+        finalized: std::sync::Mutex<Vec<String>>,
+    }
+
+    struct MemorySink {
+        store: Arc<MemoryStore>,
+        rfilename: String,
+    }
+
+    impl DownloadSink for MemorySink {
+        fn write_at<'a>(&'a self, offset: u64, bytes: &'a [u8]) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                let mut files = self.store.files.lock().unwrap();
+                let content = files.entry(self.rfilename.clone()).or_default();
+                let end = offset as usize + bytes.len();
+                if content.len() < end {
+                    content.resize(end, 0);
+                }
+                content[offset as usize..end].copy_from_slice(bytes);
+                Ok(())
+            })
+        }
+
+        fn finalize(&self) -> BoxFuture<'_, Result<(), String>> {
+            Box::pin(async move {
+                self.store.finalized.lock().unwrap().push(self.rfilename.clone());
+                Ok(())
+            })
+        }
+    }
+
+    impl SinkProvider for Arc<MemoryStore> {
+        fn open<'a>(&'a self, file: &'a FileInfo) -> BoxFuture<'a, Result<Arc<dyn DownloadSink>, String>> {
+            Box::pin(async move {
+                Ok(Arc::new(MemorySink { store: self.clone(), rfilename: file.rfilename.clone() }) as Arc<dyn DownloadSink>)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_sink_assembles_concurrent_chunks() {
+        let weights: Vec<u8> = (0..=255u8).collect();
+        let repo = MockRepo::new("org/memory", &[("model.bin", &weights), ("config.json", b"{}")]);
+        // 延迟响应让多个分块同时进行
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).delayed(Duration::from_millis(50))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 64;
+        config.chunk_size = 32;
+
+        let store = Arc::new(MemoryStore::default());
+        crate::cli::download_file(
+            config,
+            "org/memory".to_string(),
+            Some(dir.path().to_string_lossy().to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(Arc::new(store.clone())),
+            None,
+            crate::ShutdownHandle::new(),
+        ).await.unwrap();
+
+        let ranged = server.requests().iter().filter(|r| r.method == "GET" && r.range().is_some()).count();
+        assert_eq!(ranged, 8);
+        let files = store.files.lock().unwrap();
+        assert_eq!(files["model.bin"], weights);
+        assert_eq!(files["config.json"], b"{}");
+        let mut finalized = store.finalized.lock().unwrap().clone();
+        finalized.sort();
+        assert_eq!(finalized, ["config.json", "model.bin"]);
+        assert!(!dir.path().join("org/memory/model.bin").exists());
+    }
+}
@@ -0,0 +1,484 @@
+use indicatif::HumanBytes;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 吞吐低于中位数的这一比例时视为慢连接
+const SLOW_CONNECTION_RATIO: f64 = 0.25;
+
+// 每个连接数上限，超过后继续加连接通常只会触发限流
+const MAX_SUGGESTED_CONNECTIONS: usize = 16;
+
+// 一次运行中收集的下载统计，所有下载任务共享
+#[derive(Debug)]
+pub struct DownloadStats {
+    started: Instant,
+    bytes: AtomicU64,
+    requests: AtomicU64,
+    retries: AtomicU64,
+    files_downloaded: AtomicU64,
+    files_skipped: AtomicU64,
+    connections: Mutex<Vec<ConnectionStats>>,
+    files: Mutex<HashMap<String, FileStats>>,
+}
+
+// 文件数据的来源：请求的端点（scheme://host[:port]）和响应头 X-Repo-Commit 给出的 commit。
+// 经过 CDN 重定向的 LFS 文件没有这个响应头，commit 为 None
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSource {
+    pub endpoint: String,
+    pub commit: Option<String>,
+}
+
+// 单个文件在本次运行中的请求、重试和传输量；耗时从第一次请求算到最后一次收到数据
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    pub bytes: u64,
+    pub requests: u64,
+    pub retries: u64,
+    // 实际提供数据的端点和 commit；故障切换或中途重新指向时可能有多个
+    pub sources: Vec<FileSource>,
+    first_request: Instant,
+    last_byte: Option<Instant>,
+}
+
+impl FileStats {
+    fn new() -> Self {
+        Self {
+            bytes: 0,
+            requests: 0,
+            retries: 0,
+            sources: Vec::new(),
+            first_request: Instant::now(),
+            last_byte: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.last_byte
+            .map(|last| last.duration_since(self.first_request))
+            .unwrap_or_default()
+    }
+}
+
+// 分块下载中一次 Range 请求的传输情况，用于排查个别连接被 CDN 限速的问题
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    pub rfilename: String,
+    pub chunk: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl ConnectionStats {
+    pub fn bytes_per_sec(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            (self.bytes as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+}
+
+impl DownloadStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            files_downloaded: AtomicU64::new(0),
+            files_skipped: AtomicU64::new(0),
+            connections: Mutex::new(Vec::new()),
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record_bytes(&self, rfilename: &str, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.update_file(rfilename, |file| {
+            file.bytes += bytes;
+            file.last_byte = Some(Instant::now());
+        });
+    }
+
+    pub fn record_request(&self, rfilename: &str) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.update_file(rfilename, |file| file.requests += 1);
+    }
+
+    pub fn record_retry(&self, rfilename: &str) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        self.update_file(rfilename, |file| file.retries += 1);
+    }
+
+    // 记录成功响应的来源，相同来源只记一次；来源取跟随重定向后的最终地址，而不是请求的 endpoint
+    pub fn record_source(&self, rfilename: &str, response: &reqwest::Response) {
+        let endpoint = response.url().origin().ascii_serialization();
+        let commit = response.headers().get("x-repo-commit")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let source = FileSource { endpoint, commit };
+        self.update_file(rfilename, |file| {
+            if !file.sources.contains(&source) {
+                file.sources.push(source);
+            }
+        });
+    }
+
+    fn update_file(&self, rfilename: &str, update: impl FnOnce(&mut FileStats)) {
+        let mut files = self.files.lock().unwrap();
+        update(files.entry(rfilename.to_string()).or_insert_with(FileStats::new));
+    }
+
+    // 按文件名排序的单文件统计
+    pub fn files(&self) -> Vec<(String, FileStats)> {
+        let mut files: Vec<_> = self.files.lock().unwrap()
+            .iter()
+            .map(|(rfilename, file)| (rfilename.clone(), file.clone()))
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+
+    pub fn record_connection(&self, rfilename: &str, chunk: u64, bytes: u64, elapsed: Duration) {
+        self.connections.lock().unwrap().push(ConnectionStats {
+            rfilename: rfilename.to_string(),
+            chunk,
+            bytes,
+            elapsed,
+        });
+    }
+
+    pub fn connections(&self) -> Vec<ConnectionStats> {
+        self.connections.lock().unwrap().clone()
+    }
+
+    // 本次下载完成的文件
+    pub fn record_downloaded(&self) {
+        self.files_downloaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 本地已完成、不需要下载的文件
+    pub fn record_skipped(&self, count: usize) {
+        self.files_skipped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> DownloadSummary {
+        let snapshot = self.snapshot(0);
+        DownloadSummary {
+            files_downloaded: self.files_downloaded.load(Ordering::Relaxed),
+            files_skipped: self.files_skipped.load(Ordering::Relaxed),
+            bytes_downloaded: snapshot.bytes,
+            elapsed: snapshot.elapsed,
+            avg_speed: snapshot.bytes_per_sec(),
+        }
+    }
+
+    pub fn snapshot(&self, connections: usize) -> StatsSnapshot {
+        StatsSnapshot {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            elapsed: self.started.elapsed(),
+            connections,
+        }
+    }
+}
+
+impl Default for DownloadStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 一次下载的汇总结果，返回给调用方；avg_speed 为整个运行期间的平均字节每秒
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadSummary {
+    pub files_downloaded: u64,
+    pub files_skipped: u64,
+    pub bytes_downloaded: u64,
+    pub elapsed: Duration,
+    pub avg_speed: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StatsSnapshot {
+    pub bytes: u64,
+    pub requests: u64,
+    pub retries: u64,
+    pub elapsed: Duration,
+    pub connections: usize,
+}
+
+impl StatsSnapshot {
+    pub fn bytes_per_sec(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            (self.bytes as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+
+    // 重试次数占请求数的比例
+    pub fn retry_ratio(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.retries as f64 / self.requests as f64
+        }
+    }
+}
+
+// 根据吞吐和重试情况给出 connections_per_download 的建议值，None 表示保持不变
+pub fn suggest_connections(snapshot: &StatsSnapshot) -> Option<usize> {
+    let connections = snapshot.connections.max(1);
+    let ratio = snapshot.retry_ratio();
+
+    if ratio > 0.1 && connections > 1 {
+        // 重试频繁说明端点在限流或连接不稳定，减少连接数
+        Some((connections / 2).max(1))
+    } else if ratio < 0.01 && snapshot.requests > connections as u64 && connections < MAX_SUGGESTED_CONNECTIONS {
+        // 几乎没有重试且连接一直被占满，可以尝试更多连接
+        Some((connections * 2).min(MAX_SUGGESTED_CONNECTIONS))
+    } else {
+        None
+    }
+}
+
+// 运行结束后的调优报告
+pub fn tuning_report(snapshot: &StatsSnapshot) -> String {
+    let summary = format!(
+        "Used {} connections, avg {}/s, {} retries",
+        snapshot.connections,
+        HumanBytes(snapshot.bytes_per_sec()),
+        snapshot.retries,
+    );
+
+    match suggest_connections(snapshot) {
+        Some(suggested) => format!("{} — try connections_per_download={} for this endpoint", summary, suggested),
+        None => format!("{} — current settings look fine for this endpoint", summary),
+    }
+}
+
+// 各 Range 请求的吞吐概况，并列出明显慢于中位数的连接；没有分块下载时返回 None
+pub fn connection_report(connections: &[ConnectionStats]) -> Option<String> {
+    let mut rates: Vec<u64> = connections.iter().map(ConnectionStats::bytes_per_sec).collect();
+    rates.sort_unstable();
+    let median = *rates.get(rates.len() / 2)?;
+
+    let mut lines = vec![format!(
+        "{} range requests: median {}/s, slowest {}/s, fastest {}/s",
+        connections.len(),
+        HumanBytes(median),
+        HumanBytes(rates[0]),
+        HumanBytes(rates[rates.len() - 1]),
+    )];
+    for connection in connections {
+        if (connection.bytes_per_sec() as f64) < median as f64 * SLOW_CONNECTION_RATIO {
+            lines.push(format!(
+                "  slow: {} chunk {}: {} in {:.1}s ({}/s)",
+                connection.rfilename,
+                connection.chunk,
+                HumanBytes(connection.bytes),
+                connection.elapsed.as_secs_f64(),
+                HumanBytes(connection.bytes_per_sec()),
+            ));
+        }
+    }
+    Some(lines.join("\n"))
+}
+
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
+
+// 把本次运行的汇总、单文件和单连接统计写成 JSON，供事后分析或作为 CI 产物保存
+// 单个文件的来源中缺少 commit 时使用解析文件列表时的 commit
+pub fn write_stats_json(
+    path: &Path,
+    stats: &DownloadStats,
+    repo_id: &str,
+    endpoint: &str,
+    commit: Option<&str>,
+    connections: usize,
+) -> Result<(), String> {
+    let snapshot = stats.snapshot(connections);
+    let files: Vec<_> = stats.files()
+        .into_iter()
+        .map(|(rfilename, file)| serde_json::json!({
+            "rfilename": rfilename,
+            "sources": file.sources.iter()
+                .map(|source| serde_json::json!({
+                    "endpoint": source.endpoint,
+                    "commit": source.commit.as_deref().or(commit),
+                }))
+                .collect::<Vec<_>>(),
+            "bytes": file.bytes,
+            "requests": file.requests,
+            "retries": file.retries,
+            "elapsed_secs": file.elapsed().as_secs_f64(),
+            "bytes_per_sec": rate(file.bytes, file.elapsed()),
+        }))
+        .collect();
+    let connection_stats: Vec<_> = stats.connections()
+        .iter()
+        .map(|connection| serde_json::json!({
+            "rfilename": connection.rfilename,
+            "chunk": connection.chunk,
+            "bytes": connection.bytes,
+            "elapsed_secs": connection.elapsed.as_secs_f64(),
+            "bytes_per_sec": connection.bytes_per_sec(),
+        }))
+        .collect();
+
+    let report = serde_json::json!({
+        "repo_id": repo_id,
+        "endpoint": endpoint,
+        "commit": commit,
+        "elapsed_secs": snapshot.elapsed.as_secs_f64(),
+        "bytes": snapshot.bytes,
+        "requests": snapshot.requests,
+        "retries": snapshot.retries,
+        "bytes_per_sec": snapshot.bytes_per_sec(),
+        "connections_per_download": connections,
+        "files": files,
+        "connections": connection_stats,
+    });
+    let content = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("Failed to serialize stats: {}", e))?;
+    std::fs::write(path, content)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(connections: usize, requests: u64, retries: u64) -> StatsSnapshot {
+        StatsSnapshot {
+            bytes: 45 << 20,
+            requests,
+            retries,
+            elapsed: Duration::from_secs(1),
+            connections,
+        }
+    }
+
+    #[test]
+    fn suggests_connections_from_retries_and_throughput() {
+        // 重试频繁时减半
+        assert_eq!(suggest_connections(&snapshot(8, 100, 12)), Some(4));
+        assert_eq!(suggest_connections(&snapshot(3, 100, 12)), Some(1));
+        // 单连接仍在重试时不再减少
+        assert_eq!(suggest_connections(&snapshot(1, 100, 12)), None);
+        // 没有重试且连接一直占满时加倍，不超过上限
+        assert_eq!(suggest_connections(&snapshot(3, 100, 0)), Some(6));
+        assert_eq!(suggest_connections(&snapshot(12, 100, 0)), Some(MAX_SUGGESTED_CONNECTIONS));
+        assert_eq!(suggest_connections(&snapshot(MAX_SUGGESTED_CONNECTIONS, 100, 0)), None);
+        // 请求数不超过连接数时说明连接没有用满
+        assert_eq!(suggest_connections(&snapshot(4, 4, 0)), None);
+        // 少量重试时保持不变
+        assert_eq!(suggest_connections(&snapshot(4, 100, 5)), None);
+    }
+
+    #[test]
+    fn tuning_report_names_the_suggestion() {
+        assert_eq!(
+            tuning_report(&snapshot(3, 100, 0)),
+            "Used 3 connections, avg 45.00 MiB/s, 0 retries — try connections_per_download=6 for this endpoint"
+        );
+        assert_eq!(
+            tuning_report(&snapshot(4, 100, 5)),
+            "Used 4 connections, avg 45.00 MiB/s, 5 retries — current settings look fine for this endpoint"
+        );
+    }
+
+    #[test]
+    fn connection_report_lists_slow_connections() {
+        let connection = |chunk, bytes| ConnectionStats {
+            rfilename: "model.bin".to_string(),
+            chunk,
+            bytes,
+            elapsed: Duration::from_secs(1),
+        };
+        assert_eq!(connection_report(&[]), None);
+
+        let connections = [connection(0, 8 << 20), connection(1, 8 << 20), connection(2, 1 << 20), connection(3, 9 << 20)];
+        let report = connection_report(&connections).unwrap();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines[0], "4 range requests: median 8.00 MiB/s, slowest 1.00 MiB/s, fastest 9.00 MiB/s");
+        assert_eq!(lines[1..], ["  slow: model.bin chunk 2: 1.00 MiB in 1.0s (1.00 MiB/s)"]);
+    }
+
+    #[tokio::test]
+    async fn stats_json_has_files_and_totals() {
+        use crate::testing::{self, MockRepo};
+
+        let repo = MockRepo::new("org/stats", &[("config.json", b"{}"), ("model.bin", b"0123456789")]);
+        let commit = repo.info()["sha"].as_str().unwrap().to_string();
+        let server = repo.serve();
+        let dir = tempfile::tempdir().unwrap();
+        let stats_path = dir.path().join("stats.json");
+        let mut config = testing::config(&server.url());
+        config.stats_json = Some(stats_path.to_string_lossy().to_string());
+        testing::download(config, "org/stats", &dir.path().join("out")).await.unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+        assert_eq!(report["repo_id"], "org/stats");
+        assert_eq!(report["endpoint"], server.url());
+        assert_eq!(report["commit"], commit.as_str());
+        assert_eq!(report["bytes"], 12);
+        assert_eq!(report["requests"], 2);
+        assert_eq!(report["retries"], 0);
+
+        let mut files = report["files"].as_array().unwrap().clone();
+        files.sort_by_key(|file| file["rfilename"].as_str().unwrap().to_string());
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0]["rfilename"], "config.json");
+        assert_eq!(files[0]["bytes"], 2);
+        assert_eq!(files[1]["rfilename"], "model.bin");
+        assert_eq!(files[1]["bytes"], 10);
+        assert_eq!(files[1]["requests"], 1);
+        assert_eq!(files[1]["sources"][0]["endpoint"], server.url());
+    }
+
+    #[tokio::test]
+    async fn stats_json_records_the_mirror_each_file_came_from() {
+        use crate::testing::{self, MockRepo, MockServer, Response};
+
+        let repo = MockRepo::new("org/mirrored", &[("config.json", b"{}"), ("model.bin", b"0123456789")]);
+        let mirror = repo.clone().serve();
+        let mirror_url = mirror.url();
+        // 第一个镜像无法提供 model.bin，重定向到第二个镜像
+        let primary = MockServer::start(move |request| {
+            if request.path.contains("/resolve/") && request.path.ends_with("/model.bin") {
+                return Response::new(302).header("Location", format!("{}{}", mirror_url, request.path));
+            }
+            repo.respond(request).unwrap_or_else(Response::not_found)
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let stats_path = dir.path().join("stats.json");
+        let mut config = testing::config(&primary.url());
+        config.stats_json = Some(stats_path.to_string_lossy().to_string());
+        testing::download(config, "org/mirrored", &dir.path().join("out")).await.unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+        let mut files = report["files"].as_array().unwrap().clone();
+        files.sort_by_key(|file| file["rfilename"].as_str().unwrap().to_string());
+        assert_eq!(files[0]["sources"].as_array().unwrap().len(), 1);
+        assert_eq!(files[0]["sources"][0]["endpoint"], primary.url());
+        assert_eq!(files[1]["sources"].as_array().unwrap().len(), 1);
+        assert_eq!(files[1]["sources"][0]["endpoint"], mirror.url());
+        assert_eq!(mirror.downloads().len(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::types::FileInfo;
+use super::checksum::sha256_file;
+use super::layout::{local_path, PathMapping};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// 单流下载时在写入前对字节流做变换（如按行过滤文本数据集）。
+// 分块下载的写入顺序不确定，因此设置了变换时总是使用单流下载；
+// 变换后的文件大小与远端不同，已完成的文件改由 TRANSFORMED_RECORD 中的记录判断
+pub trait StreamTransform: Send + Sync {
+    fn transform(&self, chunk: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+impl<F> StreamTransform for F
+where
+    F: Fn(&[u8]) -> Vec<u8> + Send + Sync,
+{
+    fn transform(&self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        Ok(self(chunk))
+    }
+}
+
+// 变换后的文件记录在下载目录中，续传时据此判断文件是否已完成
+pub const TRANSFORMED_RECORD: &str = ".hfd-transformed.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransformedFile {
+    // 变换前远端文件的大小和摘要，远端更新后记录失效
+    pub source_size: Option<u64>,
+    #[serde(default)]
+    pub source_sha256: Option<String>,
+    #[serde(default)]
+    pub source_blob_id: Option<String>,
+    // 变换后本地文件的大小和 sha256
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl TransformedFile {
+    fn matches_source(&self, file: &FileInfo) -> bool {
+        self.source_size == file.size
+            && self.source_sha256 == file.sha256
+            && self.source_blob_id == file.blob_id
+    }
+}
+
+pub fn load_record(dir: &Path) -> BTreeMap<String, TransformedFile> {
+    std::fs::read_to_string(dir.join(TRANSFORMED_RECORD))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_record(dir: &Path, record: &BTreeMap<String, TransformedFile>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(record)
+        .map_err(|e| format!("Failed to serialize transformed files: {}", e))?;
+    std::fs::write(dir.join(TRANSFORMED_RECORD), content)
+        .map_err(|e| format!("Failed to write {}: {}", TRANSFORMED_RECORD, e))
+}
+
+// 本地文件与记录中的变换结果一致，且远端没有更新时视为已完成
+pub async fn is_transformed_complete(record: &BTreeMap<String, TransformedFile>, file: &FileInfo, path: &Path) -> bool {
+    let Some(entry) = record.get(&file.rfilename) else {
+        return false;
+    };
+    if !entry.matches_source(file) {
+        return false;
+    }
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.len() == entry.size => {}
+        _ => return false,
+    }
+    sha256_file(path).await.is_ok_and(|actual| actual.eq_ignore_ascii_case(&entry.sha256))
+}
+
+// 下载成功后记录变换结果，已有的其他文件记录保持不变
+pub async fn record_transformed(dir: &Path, files: &[FileInfo], path_map: &[PathMapping]) -> Result<(), String> {
+    let mut record = load_record(dir);
+    for file in files {
+        let path = local_path(dir, &file.rfilename, path_map);
+        // 本地不存在的文件不记录
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let size = metadata.len();
+        let sha256 = sha256_file(&path).await?;
+        record.insert(file.rfilename.clone(), TransformedFile {
+            source_size: file.size,
+            source_sha256: file.sha256.clone(),
+            source_blob_id: file.blob_id.clone(),
+            size,
+            sha256,
+        });
+    }
+    save_record(dir, &record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+    use std::sync::Arc;
+
+    async fn download_uppercase(config: crate::config::Config, dir: &Path) {
+        let uppercase: Arc<dyn StreamTransform> = Arc::new(|chunk: &[u8]| chunk.to_ascii_uppercase());
+        crate::cli::download_file(
+            config, "org/lines".to_string(), Some(dir.to_string_lossy().to_string()),
+            None, None, None, Some(uppercase), None, None, crate::ShutdownHandle::new(),
+        ).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn uppercase_transform_is_applied_to_the_written_file() {
+        let text = "first line\nsecond line\n".repeat(100);
+        let server = MockRepo::new("org/lines", &[("train.jsonl", text.as_bytes()), ("notes/readme.txt", b"hello")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        // 没有变换时会分块下载的大小，变换时仍按单流下载
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 64;
+        download_uppercase(config.clone(), dir.path()).await;
+
+        let target = dir.path().join("org/lines");
+        assert_eq!(std::fs::read_to_string(target.join("train.jsonl")).unwrap(), text.to_uppercase());
+        assert_eq!(std::fs::read(target.join("notes/readme.txt")).unwrap(), b"HELLO");
+        assert!(server.requests().iter().all(|r| r.header("range").is_none()));
+
+        // 按记录判断变换后的文件已完成，不再下载
+        let record = load_record(&target);
+        assert_eq!(record["train.jsonl"].source_size, Some(text.len() as u64));
+        let before = server.downloads().len();
+        download_uppercase(config, dir.path()).await;
+        assert_eq!(server.downloads().len(), before);
+    }
+
+    #[tokio::test]
+    async fn decompressed_file_is_complete_on_the_next_run() {
+        let server = MockRepo::new("org/packed", &[("data.bin", b"abcdef")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        let config = testing::config(&server.url());
+        let target = dir.path().join("org/packed/data.bin");
+        // 输出大小与远端不同的变换，类似解压
+        let download = || {
+            let expand: Arc<dyn StreamTransform> = Arc::new(|chunk: &[u8]| chunk.iter().flat_map(|&b| [b, b]).collect());
+            crate::cli::download_file(
+                config.clone(), "org/packed".to_string(), Some(dir.path().to_string_lossy().to_string()),
+                None, None, None, Some(expand), None, None, crate::ShutdownHandle::new(),
+            )
+        };
+
+        download().await.unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"aabbccddeeff");
+        download().await.unwrap();
+        assert_eq!(server.downloads(), ["data.bin"]);
+
+        // 本地文件被改动后与记录不符，重新下载
+        std::fs::write(&target, b"aabbccddeefX").unwrap();
+        download().await.unwrap();
+        assert_eq!(server.downloads(), ["data.bin", "data.bin"]);
+        assert_eq!(std::fs::read(&target).unwrap(), b"aabbccddeeff");
+    }
+}
+
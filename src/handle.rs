@@ -0,0 +1,36 @@
+// pyo3 0.20 的宏展开会触发较新编译器的 non_local_definitions 检查
+#![allow(non_local_definitions)]
+
+use pyo3::prelude::*;
+use crate::ShutdownHandle;
+
+// 暴露给 Python 的下载控制句柄，可在另一线程中暂停、继续或取消下载
+#[pyclass(name = "DownloadHandle")]
+#[derive(Clone, Default)]
+pub struct PyDownloadHandle {
+    pub inner: ShutdownHandle,
+}
+
+#[pymethods]
+impl PyDownloadHandle {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn pause(&self) {
+        self.inner.pause();
+    }
+
+    fn resume(&self) {
+        self.inner.resume();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    fn cancel(&self) {
+        self.inner.shutdown();
+    }
+}
@@ -1,19 +1,30 @@
 use pyo3::prelude::*;
-use tokio::sync::broadcast;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
 
 mod config;
 mod download;
+mod power;
 mod types;
 mod cli;
+mod warnings;
+mod handle;
+#[cfg(test)]
+mod testing;
 
+use handle::PyDownloadHandle;
+
+#[derive(Clone)]
 pub struct ShutdownHandle {
     tx: broadcast::Sender<()>,
+    pause_tx: Arc<watch::Sender<bool>>,
 }
 
 impl ShutdownHandle {
     pub fn new() -> Self {
         let (tx, _) = broadcast::channel(1);
-        Self { tx }
+        let (pause_tx, _) = watch::channel(false);
+        Self { tx, pause_tx: Arc::new(pause_tx) }
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<()> {
@@ -23,6 +34,29 @@ impl ShutdownHandle {
     pub fn shutdown(&self) {
         let _ = self.tx.send(());
     }
+
+    // 暂停期间写入循环会停在原地，连接和进度都保留
+    pub fn pause(&self) {
+        self.pause_tx.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.pause_tx.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.pause_tx.borrow()
+    }
+
+    pub fn subscribe_pause(&self) -> watch::Receiver<bool> {
+        self.pause_tx.subscribe()
+    }
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 fn setup_interrupt_handler(handle: ShutdownHandle) {
@@ -32,34 +66,393 @@ fn setup_interrupt_handler(handle: ShutdownHandle) {
     }).expect("Error setting Ctrl+C handler");
 }
 
+// 只有一个可用 CPU 时默认运行时只有一个工作线程，大量连接的读写任务会让进度更新明显滞后。
+// 此时仍使用两个工作线程，并提示用户
+fn new_runtime() -> PyResult<tokio::runtime::Runtime> {
+    runtime_for(std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+fn runtime_for(parallelism: usize) -> PyResult<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if parallelism < 2 {
+        warnings::warn("Only one CPU is available; progress updates may lag behind downloads. Consider lowering concurrent_downloads and connections_per_download");
+        builder.worker_threads(2);
+    }
+    builder.enable_all()
+        .build()
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))
+}
+
+// 服务器上任务通常以 SIGTERM 结束，走与 Ctrl+C 相同的中断流程，保留可续传的文件
+fn setup_terminate_handler(rt: &tokio::runtime::Runtime, handle: ShutdownHandle) {
+    rt.spawn(interrupt_on_terminate(handle));
+}
+
+async fn interrupt_on_terminate(handle: ShutdownHandle) {
+    wait_for_terminate().await;
+    println!("\nReceived termination signal, interrupting downloads...");
+    handle.shutdown();
+}
+
+#[cfg(unix)]
+async fn wait_for_terminate() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            sigterm.recv().await;
+        }
+        Err(_) => std::future::pending().await,
+    }
+}
+
+#[cfg(windows)]
+async fn wait_for_terminate() {
+    use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+
+    match (ctrl_close(), ctrl_shutdown()) {
+        (Ok(mut close), Ok(mut shutdown)) => {
+            tokio::select! {
+                _ = close.recv() => {}
+                _ = shutdown.recv() => {}
+            }
+        }
+        _ => std::future::pending().await,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+async fn wait_for_terminate() {
+    std::future::pending().await
+}
+
+// 把 Python 可调用对象 `transform(bytes) -> bytes` 作为字节流变换
+struct PyStreamTransform(PyObject);
+
+impl download::transform::StreamTransform for PyStreamTransform {
+    fn transform(&self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        Python::with_gil(|py| {
+            self.0.call1(py, (pyo3::types::PyBytes::new(py, chunk),))
+                .and_then(|result| result.extract::<Vec<u8>>(py))
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+// 把 Python 可调用对象 `callback(filename, downloaded, total)` 作为进度回调，
+// 只在调用期间持有 GIL；回调抛出的异常会中止下载。
+// 对象带有 `on_phase(phase, filename, completed, total)` 方法时也报告校验等阶段，phase 如 "verifying"
+struct PyProgressCallback(PyObject);
+
+impl download::callback::ProgressCallback for PyProgressCallback {
+    fn on_progress(&self, filename: &str, downloaded: u64, total: u64) -> Result<(), String> {
+        Python::with_gil(|py| {
+            self.0.call1(py, (filename, downloaded, total))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    fn on_phase(&self, phase: download::callback::Phase, filename: &str, completed: usize, total: usize) -> Result<(), String> {
+        Python::with_gil(|py| {
+            let Ok(on_phase) = self.0.getattr(py, "on_phase") else {
+                return Ok(());
+            };
+            on_phase.call1(py, (phase.name(), filename, completed, total))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+// with_stats 为 true 时返回包含下载目录和统计的字典，否则只返回下载目录
+fn download_result(py: Python<'_>, path: String, summary: download::stats::DownloadSummary, with_stats: bool) -> PyResult<PyObject> {
+    if !with_stats {
+        return Ok(path.into_py(py));
+    }
+    let result = pyo3::types::PyDict::new(py);
+    result.set_item("path", path)?;
+    result.set_item("files_downloaded", summary.files_downloaded)?;
+    result.set_item("files_skipped", summary.files_skipped)?;
+    result.set_item("bytes_downloaded", summary.bytes_downloaded)?;
+    result.set_item("elapsed", summary.elapsed.as_secs_f64())?;
+    result.set_item("avg_speed", summary.avg_speed)?;
+    Ok(result.into())
+}
+
 #[pyfunction]
+#[allow(clippy::too_many_arguments)]
 fn download_file(
+    py: Python<'_>,
     model_id: String,
     local_dir: Option<String>,
     include_patterns: Option<Vec<String>>,
     exclude_patterns: Option<Vec<String>>,
     hf_token: Option<String>,
+    transform: Option<PyObject>,
+    handle: Option<PyDownloadHandle>,
+    progress_callback: Option<PyObject>,
+    with_stats: Option<bool>,
+) -> PyResult<PyObject> {
+    let handle = handle.map(|h| h.inner).unwrap_or_default();
+    setup_interrupt_handler(handle.clone());
+
+    let config = config::Config::load()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+
+    let rt = new_runtime()?;
+    setup_terminate_handler(&rt, handle.clone());
+
+    let transform = transform.map(|f| Arc::new(PyStreamTransform(f)) as Arc<dyn download::transform::StreamTransform>);
+    let progress_callback = progress_callback.map(|f| Arc::new(PyProgressCallback(f)) as Arc<dyn download::callback::ProgressCallback>);
+
+    // 释放 GIL，下载线程调用 transform 和进度回调时才能重新获取
+    let result = py.allow_threads(|| {
+        rt.block_on(cli::download_file(config, model_id, local_dir, include_patterns, exclude_patterns, hf_token, transform, None, progress_callback, handle))
+    });
+    warnings::print_summary();
+    let (path, summary) = result?;
+    download_result(py, path, summary, with_stats.unwrap_or(false))
+}
+
+// 只下载仓库中的一个文件，返回本地路径
+#[pyfunction]
+fn download_single_file(
+    py: Python<'_>,
+    model_id: String,
+    path: String,
+    local_dir: Option<String>,
+    hf_token: Option<String>,
+    revision: Option<String>,
+    handle: Option<PyDownloadHandle>,
 ) -> PyResult<String> {
-    let handle = ShutdownHandle::new();
+    let handle = handle.map(|h| h.inner).unwrap_or_default();
     setup_interrupt_handler(handle.clone());
 
-    let rt = tokio::runtime::Runtime::new()
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))?;
-    
-    rt.block_on(cli::download_file(model_id, local_dir, include_patterns, exclude_patterns, hf_token, handle))
+    let mut config = config::Config::load()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+    if revision.is_some() {
+        config.revision = revision;
+    }
+
+    let rt = new_runtime()?;
+    setup_terminate_handler(&rt, handle.clone());
+
+    let result = py.allow_threads(|| {
+        rt.block_on(cli::download_single_file(config, model_id, path, local_dir, hf_token, handle))
+    });
+    warnings::print_summary();
+    result
+}
+
+// download_file 的异步版本，返回可以在已有事件循环中 await 的对象。
+// 使用 pyo3-asyncio 共享的 tokio 运行时，不安装信号处理，通过 handle 中断
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn download_async(
+    py: Python<'_>,
+    model_id: String,
+    local_dir: Option<String>,
+    include_patterns: Option<Vec<String>>,
+    exclude_patterns: Option<Vec<String>>,
+    hf_token: Option<String>,
+    transform: Option<PyObject>,
+    handle: Option<PyDownloadHandle>,
+    progress_callback: Option<PyObject>,
+    with_stats: Option<bool>,
+) -> PyResult<&PyAny> {
+    let handle = handle.map(|h| h.inner).unwrap_or_default();
+
+    let config = config::Config::load()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+
+    let transform = transform.map(|f| Arc::new(PyStreamTransform(f)) as Arc<dyn download::transform::StreamTransform>);
+    let progress_callback = progress_callback.map(|f| Arc::new(PyProgressCallback(f)) as Arc<dyn download::callback::ProgressCallback>);
+
+    // 下载的 future 不是 Send，不能直接交给运行时调度；在单独的线程上用共享运行时执行，
+    // 返回给 Python 的 future 只等待结果
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let result = pyo3_asyncio::tokio::get_runtime().block_on(
+            cli::download_file(config, model_id, local_dir, include_patterns, exclude_patterns, hf_token, transform, None, progress_callback, handle)
+        );
+        warnings::print_summary();
+        let _ = tx.send(result);
+    });
+
+    pyo3_asyncio::tokio::future_into_py(py, async move {
+        let (path, summary) = rx.await
+            .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Download thread exited unexpectedly"))??;
+        Python::with_gil(|py| download_result(py, path, summary, with_stats.unwrap_or(false)))
+    })
+}
+
+// 返回将要下载的文件列表而不下载，供调用方在下载前展示预览
+#[pyfunction]
+fn list_files(
+    py: Python<'_>,
+    model_id: String,
+    local_dir: Option<String>,
+    include_patterns: Option<Vec<String>>,
+    exclude_patterns: Option<Vec<String>>,
+    hf_token: Option<String>,
+    revision: Option<String>,
+) -> PyResult<PyObject> {
+    let mut config = config::Config::load()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+    if revision.is_some() {
+        config.revision = revision;
+    }
+
+    let rt = new_runtime()?;
+    let download_plan = py.allow_threads(|| {
+        rt.block_on(cli::list_files(config, model_id, local_dir, include_patterns, exclude_patterns, hf_token))
+    })?;
+
+    let files = pyo3::types::PyList::empty(py);
+    for planned in &download_plan.files {
+        let file = pyo3::types::PyDict::new(py);
+        file.set_item("rfilename", &planned.file.rfilename)?;
+        file.set_item("size", planned.file.size)?;
+        file.set_item("local_size", planned.local_size)?;
+        file.set_item("exists", planned.action != download::plan::PlanAction::Download)?;
+        files.append(file)?;
+    }
+
+    let result = pyo3::types::PyDict::new(py);
+    result.set_item("repo_id", &download_plan.repo_id)?;
+    result.set_item("destination", download_plan.destination.to_string_lossy().to_string())?;
+    result.set_item("files", files)?;
+    result.set_item("total_bytes", download_plan.total_bytes)?;
+    result.set_item("download_bytes", download_plan.download_bytes)?;
+    Ok(result.into())
 }
 
 #[pyfunction]
 fn main() -> PyResult<()> {
     let handle = ShutdownHandle::new();
     setup_interrupt_handler(handle.clone());
-    
-    cli::run_cli()
+
+    cli::run_cli(handle)
 }
 
 #[pymodule]
 fn hfd(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(download_file, m)?)?;
+    m.add_function(wrap_pyfunction!(download_async, m)?)?;
+    m.add_function(wrap_pyfunction!(download_single_file, m)?)?;
+    m.add_function(wrap_pyfunction!(list_files, m)?)?;
     m.add_function(wrap_pyfunction!(main, m)?)?;
+    m.add_class::<PyDownloadHandle>()?;
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::download::progress::part_path;
+    use crate::testing::{self, MockRepo, MockServer, Response};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn sigterm_leaves_a_resumable_part_file() {
+        let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        let repo = MockRepo::new("org/slow", &[("model.bin", &content)]);
+        let server = MockServer::start(move |request| {
+            let response = repo.respond(request).unwrap_or_else(Response::not_found);
+            if request.method == "GET" && request.path.contains("/resolve/") {
+                response.paced(4096, Duration::from_millis(50))
+            } else {
+                response
+            }
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("org/slow/model.bin");
+        let part = part_path(&target);
+
+        let handle = ShutdownHandle::new();
+        tokio::spawn(interrupt_on_terminate(handle.clone()));
+        // 让监听任务先注册 SIGTERM 处理，否则信号会直接结束测试进程
+        tokio::task::yield_now().await;
+
+        // 写缓冲小于文件，中断前 .hfd-part 中已有内容
+        let mut config = testing::config(&server.url());
+        config.buffer_size = 4096;
+        let download = crate::cli::download_file(
+            config.clone(), "org/slow".to_string(), Some(dir.path().to_string_lossy().to_string()),
+            None, None, None, None, None, None, handle,
+        );
+        let terminate = async {
+            let started = Instant::now();
+            while std::fs::metadata(&part).map_or(0, |m| m.len()) == 0 {
+                assert!(started.elapsed() < Duration::from_secs(10), "download never started");
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let status = std::process::Command::new("kill")
+                .args(["-TERM", &std::process::id().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        let (result, ()) = tokio::join!(download, terminate);
+
+        assert!(result.is_err());
+        assert!(!target.exists());
+        let kept = std::fs::metadata(&part).unwrap().len();
+        assert!(kept > 0 && kept < content.len() as u64, "kept {} bytes", kept);
+        assert_eq!(std::fs::read(&part).unwrap(), content[..kept as usize]);
+
+        // 再次下载从 .hfd-part 的末尾继续
+        testing::download(config, "org/slow", dir.path()).await.unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), content);
+        let resumed = server.requests().into_iter()
+            .filter(|r| r.method == "GET" && r.path.ends_with("/model.bin"))
+            .filter_map(|r| r.header("range").map(str::to_string))
+            .next_back();
+        assert_eq!(resumed, Some(format!("bytes={}-", kept)));
+    }
+
+    #[test]
+    fn progress_keeps_updating_with_one_cpu() {
+        use crate::download::callback::ProgressCallback;
+        use std::sync::{Arc, Mutex};
+
+        struct Updates(Mutex<Vec<u64>>);
+
+        impl ProgressCallback for Updates {
+            fn on_progress(&self, _filename: &str, downloaded: u64, _total: u64) -> Result<(), String> {
+                self.0.lock().unwrap().push(downloaded);
+                Ok(())
+            }
+        }
+
+        let rt = runtime_for(1).unwrap();
+        assert_eq!(rt.metrics().num_workers(), 2);
+        assert!(warnings::recorded().iter().any(|w| w.starts_with("Only one CPU is available")));
+
+        // 多个连接同时下载慢速分块时，进度回调仍在下载过程中持续收到更新
+        let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        let repo = MockRepo::new("org/single-core", &[("model.bin", &content)]);
+        let server = MockServer::start(move |request| {
+            repo.respond(request).unwrap_or_else(Response::not_found).paced(512, Duration::from_millis(20))
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = testing::config(&server.url());
+        config.parallel_download_threshold = 4096;
+        config.chunk_size = 4096;
+        config.connections_per_download = 4;
+        config.progress_refresh_ms = 50;
+        let updates = Arc::new(Updates(Mutex::new(Vec::new())));
+
+        rt.block_on(crate::cli::download_file(
+            config, "org/single-core".to_string(), Some(dir.path().to_string_lossy().to_string()),
+            None, None, None, None, None, Some(updates.clone()), ShutdownHandle::new(),
+        )).unwrap();
+
+        let updates = updates.0.lock().unwrap();
+        let partial = updates.iter().filter(|&&downloaded| downloaded > 0 && downloaded < content.len() as u64).count();
+        assert!(partial >= 3, "{:?}", updates);
+        assert_eq!(updates.last(), Some(&(content.len() as u64)));
+    }
+}
+
@@ -0,0 +1,164 @@
+use crate::ShutdownHandle;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+// 电源状态来源，便于替换为平台实现或测试用的假实现
+pub trait PowerStateProvider: Send + Sync {
+    // Some(true) 表示正在使用电池，Some(false) 表示接入电源，None 表示无法获取
+    fn on_battery(&self) -> Option<bool>;
+}
+
+pub struct SystemPowerState;
+
+#[cfg(target_os = "linux")]
+impl PowerStateProvider for SystemPowerState {
+    fn on_battery(&self) -> Option<bool> {
+        let entries = std::fs::read_dir("/sys/class/power_supply").ok()?;
+
+        let mut has_battery = false;
+        let mut ac_online = false;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+            match kind.trim() {
+                "Battery" => has_battery = true,
+                "Mains" | "USB" => {
+                    let online = std::fs::read_to_string(path.join("online")).unwrap_or_default();
+                    if online.trim() == "1" {
+                        ac_online = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // 没有电池的机器（台式机、服务器）视为无法获取
+        if !has_battery {
+            return None;
+        }
+        Some(!ac_online)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl PowerStateProvider for SystemPowerState {
+    fn on_battery(&self) -> Option<bool> {
+        None
+    }
+}
+
+// 电池监控任务，离开作用域时自动停止
+pub struct BatteryMonitor {
+    task: JoinHandle<()>,
+}
+
+impl Drop for BatteryMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// 定期检查电源状态：使用电池时暂停下载，接入电源后恢复
+pub fn spawn_battery_monitor(
+    provider: Arc<dyn PowerStateProvider>,
+    handle: ShutdownHandle,
+    interval: Duration,
+) -> BatteryMonitor {
+    let task = tokio::spawn(async move {
+        // 无法获取电源状态时不做任何事
+        if provider.on_battery().is_none() {
+            return;
+        }
+
+        let mut shutdown = handle.subscribe();
+        let mut paused_by_battery = false;
+        loop {
+            match provider.on_battery() {
+                Some(true) if !paused_by_battery => {
+                    println!("Running on battery power, pausing downloads until AC power returns...");
+                    handle.pause();
+                    paused_by_battery = true;
+                }
+                Some(false) if paused_by_battery => {
+                    println!("AC power restored, resuming downloads");
+                    handle.resume();
+                    paused_by_battery = false;
+                }
+                _ => {}
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown.recv() => break,
+            }
+        }
+
+        if paused_by_battery {
+            handle.resume();
+        }
+    });
+
+    BatteryMonitor { task }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::download::DownloadManager;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct FakePower(AtomicBool);
+
+    impl PowerStateProvider for FakePower {
+        fn on_battery(&self) -> Option<bool> {
+            Some(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn battery_pauses_writes_until_ac_returns() {
+        let power = Arc::new(FakePower(AtomicBool::new(false)));
+        let handle = ShutdownHandle::new();
+        let manager = DownloadManager::new(0, Config::default()).with_pause_signal(handle.subscribe_pause());
+        let _monitor = spawn_battery_monitor(power.clone(), handle.clone(), Duration::from_millis(5));
+
+        // 接入电源时写入循环不等待
+        tokio::time::timeout(Duration::from_millis(100), manager.wait_if_paused()).await.unwrap();
+
+        power.0.store(true, Ordering::SeqCst);
+        wait_until(|| handle.is_paused()).await;
+        assert!(tokio::time::timeout(Duration::from_millis(50), manager.wait_if_paused()).await.is_err());
+
+        power.0.store(false, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(1), manager.wait_if_paused()).await.unwrap();
+        assert!(!handle.is_paused());
+    }
+
+    #[tokio::test]
+    async fn unknown_power_state_never_pauses() {
+        struct NoBattery;
+        impl PowerStateProvider for NoBattery {
+            fn on_battery(&self) -> Option<bool> {
+                None
+            }
+        }
+
+        let handle = ShutdownHandle::new();
+        let monitor = spawn_battery_monitor(Arc::new(NoBattery), handle.clone(), Duration::from_millis(5));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(!handle.is_paused());
+        assert!(monitor.task.is_finished());
+    }
+}
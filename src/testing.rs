@@ -0,0 +1,377 @@
+// 单元测试共用的本地 HTTP 服务器和仓库模拟，只在测试中编译
+use crate::config::Config;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// 服务器收到的请求，头部名称为小写
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    // `Range: bytes=start-end`，end 缺省表示到文件末尾
+    pub fn range(&self) -> Option<(u64, Option<u64>)> {
+        let (start, end) = self.header("range")?.strip_prefix("bytes=")?.split_once('-')?;
+        Some((start.parse().ok()?, end.parse().ok()))
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    // 只发送前 n 字节就断开连接，Content-Length 仍是完整长度
+    pub truncate_at: Option<usize>,
+    // 发送响应头之前等待
+    pub delay: Duration,
+    // 每发送 n 字节等待一次，模拟慢速链路
+    pub pace: Option<(usize, Duration)>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            truncate_at: None,
+            delay: Duration::ZERO,
+            pace: None,
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200).body(body)
+    }
+
+    pub fn json(value: serde_json::Value) -> Self {
+        Self::ok(value.to_string()).header("Content-Type", "application/json")
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(404)
+            .header("X-Error-Code", "RepoNotFound")
+            .body(r#"{"error":"Repository not found"}"#)
+    }
+
+    // 按请求的 Range 返回文件内容，有 Range 时返回 206
+    pub fn file(request: &Request, content: &[u8]) -> Self {
+        let len = content.len() as u64;
+        match request.range() {
+            Some((start, end)) if start < len => {
+                let end = end.map_or(len - 1, |end| end.min(len - 1));
+                Self::new(206)
+                    .header("Content-Range", format!("bytes {}-{}/{}", start, end, len))
+                    .body(&content[start as usize..=end as usize])
+            }
+            Some(_) => Self::new(416).header("Content-Range", format!("bytes */{}", len)),
+            None => Self::ok(content),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn truncated(mut self, at: usize) -> Self {
+        self.truncate_at = Some(at);
+        self
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn paced(mut self, bytes: usize, interval: Duration) -> Self {
+        self.pace = Some((bytes, interval));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+struct ServerState {
+    requests: Mutex<Vec<Request>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+// 每个连接一个线程的 HTTP/1.1 服务器，每个响应后关闭连接；drop 时停止接受新连接
+pub struct MockServer {
+    port: u16,
+    state: Arc<ServerState>,
+}
+
+impl MockServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(ServerState {
+            requests: Mutex::new(Vec::new()),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_state.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let state = server_state.clone();
+                let handler = handler.clone();
+                std::thread::spawn(move || serve_connection(stream, &state, &*handler));
+            }
+        });
+
+        Self { port, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    // GET /resolve/ 请求的文件路径，按请求顺序，重试和分块会重复出现
+    pub fn downloads(&self) -> Vec<String> {
+        self.requests().into_iter()
+            .filter(|r| r.method == "GET")
+            .filter_map(|r| Some(r.path.split_once("/resolve/")?.1.split_once('/')?.1.to_string()))
+            .collect()
+    }
+
+    // 同时在处理中的请求数的最大值
+    pub fn max_in_flight(&self) -> usize {
+        self.state.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        // 发起一个连接让 accept 返回
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+fn serve_connection(stream: TcpStream, state: &ServerState, handler: &Handler) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let request = Request { method, path, headers };
+    state.requests.lock().unwrap().push(request.clone());
+
+    let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    state.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+    let response = handler(&request);
+    std::thread::sleep(response.delay);
+    let _ = write_response(stream, &request, &response);
+    state.in_flight.fetch_sub(1, Ordering::SeqCst);
+}
+
+fn write_response(mut stream: TcpStream, request: &Request, response: &Response) -> std::io::Result<()> {
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+    stream.write_all(head.as_bytes())?;
+    if request.method == "HEAD" {
+        return Ok(());
+    }
+
+    let body = &response.body[..response.truncate_at.unwrap_or(response.body.len()).min(response.body.len())];
+    match response.pace {
+        Some((bytes, interval)) => {
+            for piece in body.chunks(bytes.max(1)) {
+                stream.write_all(piece)?;
+                stream.flush()?;
+                std::thread::sleep(interval);
+            }
+        }
+        None => stream.write_all(body)?,
+    }
+    stream.flush()?;
+    stream.shutdown(std::net::Shutdown::Both)
+}
+
+// Hub 上的一个仓库：仓库信息接口返回 siblings（带 blobId 和 lfs.sha256），/resolve/ 返回文件内容
+#[derive(Clone)]
+pub struct MockRepo {
+    pub repo_id: String,
+    pub is_dataset: bool,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl MockRepo {
+    pub fn new(repo_id: &str, files: &[(&str, &[u8])]) -> Self {
+        Self {
+            repo_id: repo_id.to_string(),
+            is_dataset: false,
+            files: files.iter().map(|(path, content)| (path.to_string(), content.to_vec())).collect(),
+        }
+    }
+
+    pub fn content(&self, path: &str) -> &[u8] {
+        &self.files.iter().find(|(p, _)| p == path).unwrap().1
+    }
+
+    // 文件列表决定 ETag 和 commit，内容变化后条件请求不再返回 304
+    pub fn etag(&self) -> String {
+        let mut hasher = sha2::Sha256::new();
+        for (path, content) in &self.files {
+            hasher.update(path.as_bytes());
+            hasher.update(content);
+        }
+        format!("\"{:x}\"", hasher.finalize())
+    }
+
+    pub fn info(&self) -> serde_json::Value {
+        let siblings: Vec<_> = self.files.iter()
+            .map(|(path, content)| serde_json::json!({
+                "rfilename": path,
+                "size": content.len(),
+                "blobId": git_blob_sha1(content),
+                "lfs": { "sha256": sha256_hex(content), "size": content.len() },
+            }))
+            .collect();
+        serde_json::json!({
+            "id": self.repo_id,
+            "sha": self.etag().trim_matches('"')[..40],
+            "siblings": siblings,
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        if self.is_dataset { "datasets" } else { "models" }
+    }
+
+    // 本仓库的请求返回响应，其他请求返回 None
+    pub fn respond(&self, request: &Request) -> Option<Response> {
+        let path = request.path.split('?').next().unwrap_or_default();
+        if path.starts_with(&format!("/api/{}/{}", self.kind(), self.repo_id)) {
+            let etag = self.etag();
+            if request.header("if-none-match") == Some(etag.as_str()) {
+                return Some(Response::new(304).header("ETag", etag));
+            }
+            return Some(Response::json(self.info()).header("ETag", etag));
+        }
+        let prefix = if self.is_dataset {
+            format!("/datasets/{}/resolve/", self.repo_id)
+        } else {
+            format!("/{}/resolve/", self.repo_id)
+        };
+        let (_, rfilename) = path.strip_prefix(&prefix)?.split_once('/')?;
+        Some(match self.files.iter().find(|(p, _)| p == rfilename) {
+            Some((_, content)) => Response::file(request, content),
+            None => Response::new(404).header("X-Error-Code", "EntryNotFound"),
+        })
+    }
+
+    // 只提供本仓库的服务器
+    pub fn serve(self) -> MockServer {
+        MockServer::start(move |request| self.respond(request).unwrap_or_else(Response::not_found))
+    }
+}
+
+pub fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", sha2::Sha256::digest(content))
+}
+
+pub fn git_blob_sha1(content: &[u8]) -> String {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    format!("{:x}", hasher.finalize())
+}
+
+// 指向模拟服务器的配置。格式化 PyErr（如解析失败时的警告）需要解释器，这里一并初始化
+pub fn config(endpoint: &str) -> Config {
+    pyo3::prepare_freethreaded_python();
+    Config {
+        endpoint: endpoint.to_string(),
+        max_retries: 3,
+        size_probe_retries: 0,
+        ..Config::default()
+    }
+}
+
+// 不经过 Python 读取 PyErr 的异常信息
+pub fn error_message(error: pyo3::PyErr) -> String {
+    pyo3::prepare_freethreaded_python();
+    pyo3::Python::with_gil(|py| error.value(py).to_string())
+}
+
+// 下载整个仓库到 dir，返回下载目录和统计
+pub async fn download(config: Config, repo_id: &str, dir: &std::path::Path) -> pyo3::PyResult<(String, crate::download::stats::DownloadSummary)> {
+    download_filtered(config, repo_id, dir, &[], &[]).await
+}
+
+// 按 include/exclude 模式下载，空列表表示不过滤
+pub async fn download_filtered(
+    config: Config,
+    repo_id: &str,
+    dir: &std::path::Path,
+    include: &[&str],
+    exclude: &[&str],
+) -> pyo3::PyResult<(String, crate::download::stats::DownloadSummary)> {
+    let patterns = |patterns: &[&str]| (!patterns.is_empty()).then(|| patterns.iter().map(|p| p.to_string()).collect());
+    crate::cli::download_file(
+        config,
+        repo_id.to_string(),
+        Some(dir.to_string_lossy().to_string()),
+        patterns(include),
+        patterns(exclude),
+        None,
+        None,
+        None,
+        None,
+        crate::ShutdownHandle::new(),
+    ).await
+}
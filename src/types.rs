@@ -4,6 +4,15 @@ use serde::{Deserialize, Serialize};
 pub struct FileInfo {
     pub rfilename: String,
     pub size: Option<u64>,
+    // LFS 文件的 SHA256
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // git blob 的 SHA1
+    #[serde(default)]
+    pub blob_id: Option<String>,
+    // 服务器返回的 Last-Modified（HTTP 日期格式）
+    #[serde(default)]
+    pub last_modified: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +20,12 @@ pub struct RepoInfo {
     pub model_endpoint: Option<String>,
     pub dataset_endpoint: Option<String>,
     pub files: Vec<FileInfo>,
+    // 仓库信息对应的 commit（API 返回的 sha）
+    #[serde(default)]
+    pub commit: Option<String>,
+    // 仓库信息响应的 ETag，用于下次运行时的条件请求
+    #[serde(default)]
+    pub etag: Option<String>,
 }
 
 impl RepoInfo {
@@ -23,4 +38,48 @@ impl RepoInfo {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Auth {
     pub token: Option<String>,
-} 
\ No newline at end of file
+} 
+
+// 按顺序取 token：显式传入（命令行或配置文件）、HF_TOKEN 环境变量、`huggingface-cli login` 保存的 token 文件
+pub fn resolve_token(explicit: Option<String>) -> Option<String> {
+    explicit
+        .or_else(|| std::env::var("HF_TOKEN").ok())
+        .or_else(|| {
+            let hf_home = std::env::var("HF_HOME")
+                .unwrap_or_else(|_| shellexpand::tilde("~/.cache/huggingface").into_owned());
+            std::fs::read_to_string(std::path::Path::new(&hf_home).join("token")).ok()
+        })
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 环境变量是进程级的，所有情况放在同一个测试中依次检查
+    #[test]
+    fn token_falls_back_to_env_then_token_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved: Vec<_> = ["HF_TOKEN", "HF_HOME"].iter().map(|name| (name, std::env::var(name).ok())).collect();
+        std::env::remove_var("HF_TOKEN");
+        std::env::set_var("HF_HOME", dir.path());
+
+        assert_eq!(resolve_token(None), None);
+        std::fs::write(dir.path().join("token"), "hf_file\n").unwrap();
+        assert_eq!(resolve_token(None).as_deref(), Some("hf_file"));
+        std::env::set_var("HF_TOKEN", "hf_env");
+        assert_eq!(resolve_token(None).as_deref(), Some("hf_env"));
+        assert_eq!(resolve_token(Some("hf_explicit".to_string())).as_deref(), Some("hf_explicit"));
+        // 空白的 token 视为没有 token
+        assert_eq!(resolve_token(Some("  ".to_string())), None);
+
+        for (name, value) in saved {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+}
+
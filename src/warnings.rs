@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+// 运行期间收集的警告，结束时统一输出，避免被进度条刷掉
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// 记录一条警告，同时立即输出
+pub fn warn(message: impl Into<String>) {
+    let message = message.into();
+    println!("Warning: {}", message);
+    WARNINGS.lock().unwrap().push(message);
+}
+
+// 取出并清空已收集的警告
+pub fn take() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+// 已收集的警告（不清空），测试并行运行时不会互相取走
+#[cfg(test)]
+pub fn recorded() -> Vec<String> {
+    WARNINGS.lock().unwrap().clone()
+}
+
+// 输出汇总，没有警告时不输出
+pub fn print_summary() {
+    if let Some(summary) = format_summary(&take()) {
+        println!("\n{}", summary);
+    }
+}
+
+fn format_summary(warnings: &[String]) -> Option<String> {
+    if warnings.is_empty() {
+        return None;
+    }
+
+    let mut summary = format!("{} warning(s):", warnings.len());
+    for warning in warnings {
+        summary.push_str(&format!("\n  - {}", warning));
+    }
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRepo};
+
+    #[tokio::test]
+    async fn warnings_during_a_download_reach_the_summary() {
+        let server = MockRepo::new("org/warned", &[("docs/", b""), ("config.json", b"{}")]).serve();
+        let dir = tempfile::tempdir().unwrap();
+        testing::download(testing::config(&server.url()), "org/warned", dir.path()).await.unwrap();
+
+        // 其他测试也可能同时记录警告，只检查本次的那一条
+        let summary = format_summary(&recorded()).unwrap();
+        assert!(summary.contains(" warning(s):"), "{}", summary);
+        assert!(summary.contains("\n  - Skipping 'docs/': not a file path"), "{}", summary);
+    }
+
+    #[test]
+    fn no_summary_without_warnings() {
+        assert_eq!(format_summary(&[]), None);
+    }
+}